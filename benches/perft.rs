@@ -1,6 +1,8 @@
 use criterion::{Criterion, SamplingMode, criterion_group, criterion_main};
 use luna_chess::board::Board;
 use luna_chess::movegen::MoveGen;
+use luna_chess::perft::perft_parallel;
+use std::thread;
 use std::time::Duration;
 
 fn perft_bench(c: &mut Criterion) {
@@ -34,5 +36,47 @@ fn perft_bench(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, perft_bench);
+fn parallel_perft_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Parallel perft");
+    let board = Board::default();
+    let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    group
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat)
+        .measurement_time(Duration::from_secs(20));
+    for threads in [1, 2, 4, 8].into_iter().filter(|&t| t <= max_threads) {
+        group.bench_function(format!("6/{threads} threads"), |b| {
+            b.iter(|| {
+                perft_parallel(&board, 6, threads);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn make_move_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Make move");
+    let board = Board::default();
+
+    group
+        .warm_up_time(Duration::from_secs(1))
+        .measurement_time(Duration::from_secs(10));
+    for depth in 4..=5 {
+        group.bench_function(format!("copy-make/{depth}"), |b| {
+            b.iter(|| {
+                MoveGen::perft_test(&board, depth);
+            })
+        });
+        group.bench_function(format!("make-unmake/{depth}"), |b| {
+            let mut board = board.clone();
+            b.iter(|| {
+                MoveGen::perft_test_in_place(&mut board, depth);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, perft_bench, parallel_perft_bench, make_move_bench);
 criterion_main!(benches);
@@ -0,0 +1,8 @@
+use luna_chess::uci::{Output, Uci};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+fn main() -> anyhow::Result<()> {
+    let output: Output = Arc::new(Mutex::new(io::stdout()));
+    Uci::new(output).run(io::stdin().lock())
+}
@@ -0,0 +1,9 @@
+use luna_chess::uci::Output;
+use luna_chess::xboard::Xboard;
+use std::io::{self, BufReader};
+use std::sync::{Arc, Mutex};
+
+fn main() -> anyhow::Result<()> {
+    let output: Output = Arc::new(Mutex::new(io::stdout()));
+    Xboard::new(output).run(BufReader::new(io::stdin()))
+}
@@ -0,0 +1,101 @@
+use luna_chess::epd::Epd;
+use luna_chess::perft::perft_parallel;
+use std::env;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// `perftsuite <file> [max-depth] [--threads <n>]`: checks the perft counts of every
+/// position of a perftsuite-style EPD file, like `<fen> ;D1 20 ;D2 400`, up to
+/// `max-depth`. Blank lines and lines starting with `#` are skipped.
+///
+/// Exits with a non-zero status if any count is wrong, any line can't be read or no
+/// count was checked at all.
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut threads = 1;
+    if let Some(idx) = args.iter().position(|arg| arg == "--threads") {
+        threads = match args.get(idx + 1).and_then(|s| s.parse().ok()) {
+            Some(threads) if threads > 0 => threads,
+            _ => {
+                eprintln!("--threads expects a positive number");
+                process::exit(2);
+            }
+        };
+        args.drain(idx..idx + 2);
+    }
+
+    let Some(path) = args.first() else {
+        eprintln!("usage: perftsuite <file> [max-depth] [--threads <n>]");
+        process::exit(2);
+    };
+    let max_depth = match args.get(1).map(|s| s.parse()) {
+        Some(Ok(depth)) if depth > 0 => depth,
+        Some(_) => {
+            eprintln!("max-depth expects a positive number");
+            process::exit(2);
+        }
+        None => usize::MAX,
+    };
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read '{}': {}", path, err);
+        process::exit(2);
+    });
+
+    let (mut passed, mut failed) = (0, 0);
+    let start = Instant::now();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let epd = match Epd::from_str(line) {
+            Ok(epd) => epd,
+            Err(err) => {
+                println!("line {}: FAIL {:#}", idx + 1, err);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for (depth, expected) in epd.perft_counts() {
+            if depth > max_depth {
+                continue;
+            }
+            let start = Instant::now();
+            let nodes = perft_parallel(epd.board(), depth, threads) as u64;
+            let nps = nodes as f64 / start.elapsed().as_secs_f64().max(1e-9);
+
+            if nodes == expected {
+                passed += 1;
+                println!("line {}: D{} {} ok ({:.0} nps)", idx + 1, depth, nodes, nps);
+            } else {
+                failed += 1;
+                println!(
+                    "line {}: D{} FAIL expected {}, got {} in {}",
+                    idx + 1,
+                    depth,
+                    expected,
+                    nodes,
+                    epd.board()
+                );
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{} passed, {} failed in {:?}",
+        passed,
+        failed,
+        start.elapsed()
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+    if passed == 0 {
+        eprintln!("no perft count was checked");
+        process::exit(1);
+    }
+}
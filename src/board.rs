@@ -1,17 +1,46 @@
 use crate::bitboard::BitBoard;
+use crate::board_builder::BoardBuilder;
 use crate::castle_rights::CastleRights;
 use crate::chess_move::ChessMove;
 use crate::color::Color;
+use crate::fen::{self, FenError};
 use crate::file::{ALL_FILES, File};
 use crate::magic;
-use crate::pieces::{ALL_PIECES, Piece};
+use crate::movegen::MoveGen;
+use crate::piece_moves::*;
+use crate::pieces::{ALL_PIECES, PROMOTION_PIECES, Piece};
 use crate::rank::{ALL_RANKS, Rank};
 use crate::square::Square;
+use crate::zobrist;
 
 use anyhow::{Error, bail};
 use std::fmt;
 use std::str::FromStr;
 
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55AA55AA55AA55);
+
+/// The state of the game as seen from a single position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardStatus {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+}
+
+/// The state lost by [`Board::make_move_in_place`], needed to take the move back.
+#[derive(Debug, Clone, Copy)]
+pub struct UndoInfo {
+    captured: Option<Piece>,
+    en_passant: Option<Square>,
+    castle_rights: CastleRights,
+    pinned_bitboard: BitBoard,
+    checkers_bitboard: BitBoard,
+    hash: u64,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    castled: bool,
+}
+
 #[derive(Clone)]
 pub struct Board {
     pieces_bitboards: [BitBoard; 6],
@@ -22,6 +51,10 @@ pub struct Board {
     castle_rights: CastleRights,
     pinned_bitboard: BitBoard,
     checkers_bitboard: BitBoard,
+    hash: u64,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    chess960: bool,
 }
 
 impl Board {
@@ -36,32 +69,51 @@ impl Board {
             castle_rights: CastleRights::default(),
             pinned_bitboard: BitBoard(0),
             checkers_bitboard: BitBoard(0),
+            hash: 0,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
         }
     }
 
     #[inline(always)]
-    fn place_piece(&mut self, square: Square, piece: Piece, color: Color) {
+    pub(crate) fn place_piece(&mut self, square: Square, piece: Piece, color: Color) {
         let bitboard = BitBoard::from_square(square);
         self.xor(piece, bitboard, color);
     }
 
+    /// Toggles `piece` of `color` on the single square set in `bitboard`,
+    /// keeping the zobrist hash in sync. `bitboard` must have exactly one bit set.
     #[inline(always)]
-    pub fn xor(&mut self, piece: Piece, bitboard: BitBoard, color: Color) {
+    pub(crate) fn xor(&mut self, piece: Piece, bitboard: BitBoard, color: Color) {
+        debug_assert!(bitboard.0.count_ones() == 1);
         self.pieces_bitboards[piece.to_index()] ^= bitboard;
         self.colors_bitboards[color.to_index()] ^= bitboard;
         self.combined_bitboard ^= bitboard;
+        self.hash ^= zobrist::get_piece_key(piece, bitboard.to_square(), color);
     }
 
     #[inline(always)]
-    fn set_side(&mut self, color: Color) {
+    pub(crate) fn set_side(&mut self, color: Color) {
         self.side_to_move = color;
     }
 
     #[inline(always)]
-    fn set_castling_rights(&mut self, rights: CastleRights) {
+    pub(crate) fn set_castling_rights(&mut self, rights: CastleRights) {
         self.castle_rights = rights;
     }
 
+    #[inline(always)]
+    pub(crate) fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_move_counters(&mut self, halfmove_clock: u16, fullmove_number: u16) {
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number;
+    }
+
     #[inline(always)]
     fn set_en_passant(&mut self, square: Square) {
         // only set en_passatn if the pawn ca acttually be captured next move
@@ -72,6 +124,7 @@ impl Board {
         .is_empty()
         {
             self.en_passant = Some(square);
+            self.hash ^= zobrist::get_en_passant_key(square.get_file());
         }
     }
 
@@ -105,7 +158,7 @@ impl Board {
     }
 
     #[inline(always)]
-    fn get_piece_and_color(&self, square: Square) -> Option<(Piece, Color)> {
+    pub fn get_piece_and_color(&self, square: Square) -> Option<(Piece, Color)> {
         let piece = self.get_piece(square)?;
         let color = self.get_color(square)?;
         Some((piece, color))
@@ -156,6 +209,85 @@ impl Board {
         self.castle_rights
     }
 
+    /// Returns true if castling moves are encoded as the king capturing its own rook,
+    /// as in Chess960, instead of the king moving two squares.
+    #[inline(always)]
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// Returns the number of halfmoves since the last capture or pawn move.
+    #[inline(always)]
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// Returns the number of the current full move, starting at 1 and
+    /// incremented after each Black move.
+    #[inline(always)]
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    /// Returns whether the side to move has been checkmated or stalemated.
+    #[inline(always)]
+    pub fn status(&self) -> BoardStatus {
+        if MoveGen::new_legal(self).len() != 0 {
+            BoardStatus::Ongoing
+        } else if self.checkers_bitboard.is_empty() {
+            BoardStatus::Stalemate
+        } else {
+            BoardStatus::Checkmate
+        }
+    }
+
+    /// Returns true if neither side can possibly checkmate: K vs K, K+minor vs K,
+    /// or kings plus any number of bishops all standing on the same square color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let kings = self.get_piece_bitboard(Piece::King);
+        let bishops = self.get_piece_bitboard(Piece::Bishop);
+        let knights = self.get_piece_bitboard(Piece::Knight);
+        let others = self.combined_bitboard ^ kings;
+
+        if others.is_empty() {
+            return true;
+        }
+
+        if others.0.count_ones() == 1 && others == (bishops | knights) {
+            return true;
+        }
+
+        others == bishops
+            && ((bishops & DARK_SQUARES).is_empty() || (bishops & !DARK_SQUARES).is_empty())
+    }
+
+    /// Returns the zobrist hash of the position.
+    #[inline(always)]
+    pub fn get_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Computes the zobrist hash of the position from scratch.
+    fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for square in self.combined_bitboard.get_squares() {
+            let (piece, color) = self.get_piece_and_color(square).unwrap();
+            hash ^= zobrist::get_piece_key(piece, square, color);
+        }
+
+        hash ^= zobrist::get_castles_key(self.castle_rights);
+
+        if let Some(square) = self.en_passant {
+            hash ^= zobrist::get_en_passant_key(square.get_file());
+        }
+
+        if self.side_to_move == Color::Black {
+            hash ^= zobrist::get_side_key();
+        }
+
+        hash
+    }
+
     #[inline(always)]
     fn update_attacked_bitboards(&mut self) {
         self.pinned_bitboard = BitBoard(0);
@@ -193,215 +325,482 @@ impl Board {
         );
     }
 
+    /// Parses a FEN string, only rejecting the positions the move generator can't handle:
+    /// each side must have exactly one king and at most 16 pieces, no pawn may stand on
+    /// its promotion rank and the side not to move must not be in check.
+    /// Inconsistent castling rights and en passant squares are dropped instead of rejected.
+    ///
+    /// Meant for analysis tools that need odd setups, like pawns on their first rank or
+    /// more than 8 pawns per side.
+    pub fn from_str_lenient(fen: &str) -> Result<Self, FenError> {
+        BoardBuilder::from_str(fen)?.build_lenient()
+    }
+
+    /// Checks that the position can be played on, then sets the en passant square and
+    /// computes the pins, checkers and hash. In lenient mode only the checks the move
+    /// generator depends on are enforced, and inconsistent state is dropped instead.
+    pub(crate) fn validate(
+        &mut self,
+        en_passant: Option<Square>,
+        strict: bool,
+    ) -> Result<(), FenError> {
+        // the fullmove number starts at 1
+        if strict && self.fullmove_number == 0 {
+            return Err(FenError::InvalidFullmoveNumber("0".to_string()));
+        }
+
+        for color in [Color::White, Color::Black] {
+            let pieces = self.get_color_bitboard(color);
+            match (self.get_piece_bitboard(Piece::King) & pieces)
+                .0
+                .count_ones()
+            {
+                0 => return Err(FenError::MissingKing(color)),
+                1 => {}
+                _ => return Err(FenError::TooManyKings(color)),
+            }
+
+            // the move lists can't hold the moves of more than 16 pieces
+            let pawns = (self.get_piece_bitboard(Piece::Pawn) & pieces)
+                .0
+                .count_ones();
+            if pieces.0.count_ones() > 16 || (strict && pawns > 8) {
+                return Err(FenError::TooManyPieces(color));
+            }
+
+            let mut back_ranks = magic::get_rank_bitboard(color.promotion_rank());
+            if strict {
+                back_ranks |= magic::get_rank_bitboard(color.starting_rank());
+            }
+            let pawns = self.get_piece_bitboard(Piece::Pawn) & pieces & back_ranks;
+            if !pawns.is_empty() {
+                return Err(FenError::PawnOnBackRank(pawns.to_square()));
+            }
+        }
+
+        for (color, kingside, queenside) in [(Color::White, 'K', 'Q'), (Color::Black, 'k', 'q')] {
+            let pieces = self.get_color_bitboard(color);
+            let rights = self.castle_rights;
+            let king_file = rights.king_file(color).to_index();
+            let kingside_file = rights.kingside_rook_file(color).to_index();
+            let queenside_file = rights.queenside_rook_file(color).to_index();
+            let king = self.get_piece_bitboard(Piece::King) & pieces;
+            let rooks = self.get_piece_bitboard(Piece::Rook) & pieces;
+            // outside Chess960 castling moves the king two squares, so it must start on e
+            let king_home = king == BitBoard::from_square(rights.king_square(color))
+                && (self.chess960 || rights.king_file(color) == File::E);
+            let kingside_rook = !(rooks & BitBoard::from_square(rights.kingside_rook(color)))
+                .is_empty()
+                && kingside_file > king_file;
+            let queenside_rook = !(rooks & BitBoard::from_square(rights.queenside_rook(color)))
+                .is_empty()
+                && queenside_file < king_file;
+
+            if rights.has_kingside(color) && !(king_home && kingside_rook) {
+                if strict {
+                    return Err(FenError::CastleRightsMismatch(kingside));
+                }
+                self.castle_rights.remove_kingside(color);
+            }
+            if rights.has_queenside(color) && !(king_home && queenside_rook) {
+                if strict {
+                    return Err(FenError::CastleRightsMismatch(queenside));
+                }
+                self.castle_rights.remove_queenside(color);
+            }
+        }
+
+        if let Some(square) = en_passant {
+            let side = self.side_to_move;
+            // the pawn that just moved two squares, and the squares it went through
+            let rank = (!side).starting_rank().forward(!side).forward(!side);
+            let valid = square.get_rank() == rank && {
+                let pawn = square.forward(!side).unwrap();
+                let origin = square.forward(side).unwrap();
+                self.get_piece(pawn) == Some(Piece::Pawn)
+                    && self.get_color(pawn) == Some(!side)
+                    && (self.combined_bitboard
+                        & (BitBoard::from_square(square) | BitBoard::from_square(origin)))
+                    .is_empty()
+            };
+
+            if valid {
+                self.side_to_move = !side;
+                self.set_en_passant(square);
+                self.side_to_move = side;
+            } else if strict {
+                return Err(FenError::InvalidEnPassant(square));
+            }
+        }
+
+        // the move generator would let the king be captured
+        let mut opponent = self.clone();
+        opponent.side_to_move = !self.side_to_move;
+        opponent.update_attacked_bitboards();
+        if !opponent.checkers_bitboard.is_empty() {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        self.update_attacked_bitboards();
+        self.hash = self.compute_hash();
+
+        Ok(())
+    }
+
+    /// Checks whether `m` is a legal move in this position, without generating
+    /// the whole move list.
+    pub fn is_legal(&self, m: ChessMove) -> bool {
+        let color = self.side_to_move;
+        let my_pieces = self.get_color_bitboard(color);
+        let source_bb = BitBoard::from_square(m.source);
+        let dest_bb = BitBoard::from_square(m.dest);
+
+        let piece = match self.get_piece(m.source) {
+            Some(piece) if !(my_pieces & source_bb).is_empty() => piece,
+            _ => return false,
+        };
+
+        let promotes = piece == Piece::Pawn && m.dest.get_rank() == color.promotion_rank();
+        match m.promotion {
+            Some(promotion) if !promotes || !PROMOTION_PIECES.contains(&promotion) => {
+                return false;
+            }
+            None if promotes => return false,
+            _ => {}
+        }
+
+        let combined = self.combined_bitboard;
+        let mask = !my_pieces;
+        let king_square = self.get_king_square(color);
+        let checkers = self.checkers_bitboard;
+
+        if piece == Piece::King {
+            if !(KingMoves::pseudo_legals(m.source, color, combined, mask) & dest_bb).is_empty() {
+                return KingMoves::legal_move(self, m.dest);
+            }
+            return checkers.is_empty() && !(KingMoves::legal_castles(self) & dest_bb).is_empty();
+        }
+
+        if checkers.0.count_ones() > 1 {
+            return false;
+        }
+
+        if piece == Piece::Pawn && Some(m.dest) == self.en_passant {
+            return !magic::get_pawn_attacks(m.source, color, dest_bb).is_empty()
+                && PawnMoves::legal_ep_move(self, m.source, m.dest);
+        }
+
+        let moves = match piece {
+            Piece::Pawn => PawnMoves::pseudo_legals(m.source, color, combined, mask),
+            Piece::Knight => KnightMoves::pseudo_legals(m.source, color, combined, mask),
+            Piece::Bishop => BishopMoves::pseudo_legals(m.source, color, combined, mask),
+            Piece::Rook => RookMoves::pseudo_legals(m.source, color, combined, mask),
+            Piece::Queen => QueenMoves::pseudo_legals(m.source, color, combined, mask),
+            Piece::King => unreachable!(),
+        };
+        if (moves & dest_bb).is_empty() {
+            return false;
+        }
+
+        let pinned = !(self.pinned_bitboard & source_bb).is_empty();
+        if checkers.is_empty() {
+            !pinned || !(magic::get_line(m.source, king_square) & dest_bb).is_empty()
+        } else {
+            !pinned
+                && !((magic::get_between(checkers.to_square(), king_square) ^ checkers) & dest_bb)
+                    .is_empty()
+        }
+    }
+
+    /// Plays `m` like [`Board::make_move`], but fails instead of panicking
+    /// or corrupting the board when the move is illegal.
+    pub fn try_make_move(&self, m: ChessMove) -> Result<Board, Error> {
+        if !self.is_legal(m) {
+            bail!("illegal move {:?} in position {}", m, self);
+        }
+        Ok(self.make_move(m))
+    }
+
+    /// Parses a move in UCI coordinate notation and checks that it is legal.
+    ///
+    /// Castling is accepted both as the king moving two squares and as the king
+    /// capturing its own rook, and returned in the encoding of this board.
+    pub fn parse_uci_move(&self, uci: &str) -> Result<ChessMove, Error> {
+        let m = ChessMove::from_str(uci)?;
+        if self.is_legal(m) {
+            return Ok(m);
+        }
+
+        let color = self.side_to_move;
+        if m.promotion.is_none() && m.source == self.get_king_square(color) {
+            let rights = self.castle_rights;
+            for (rook, king_dest) in [
+                (rights.kingside_rook(color), File::G),
+                (rights.queenside_rook(color), File::C),
+            ] {
+                let king_dest = Square::new(color.starting_rank(), king_dest);
+                if m.dest != rook && m.dest != king_dest {
+                    continue;
+                }
+
+                let castle =
+                    ChessMove::new(m.source, if self.chess960 { rook } else { king_dest }, None);
+                if self.is_legal(castle) {
+                    return Ok(castle);
+                }
+            }
+        }
+
+        bail!("illegal move {} in position {}", uci, self)
+    }
+
+    /// Passes the turn to the opponent without moving, for null-move pruning and
+    /// threat detection. The counters advance like after a quiet move.
+    /// Returns `None` when the side to move is in check.
+    #[inline(always)]
+    pub fn null_move(&self) -> Option<Board> {
+        if !self.checkers_bitboard.is_empty() {
+            return None;
+        }
+
+        let mut result = self.clone();
+        if let Some(square) = result.en_passant.take() {
+            result.hash ^= zobrist::get_en_passant_key(square.get_file());
+        }
+        result.halfmove_clock = result.halfmove_clock.saturating_add(1);
+        if self.side_to_move == Color::Black {
+            result.fullmove_number = result.fullmove_number.saturating_add(1);
+        }
+        result.side_to_move = !self.side_to_move;
+        result.hash ^= zobrist::get_side_key();
+        result.update_attacked_bitboards();
+        Some(result)
+    }
+
     #[inline(always)]
     pub fn make_move(&self, m: ChessMove) -> Board {
         let mut result = self.clone();
-        result.en_passant = None;
-        result.checkers_bitboard = BitBoard(0);
-        result.pinned_bitboard = BitBoard(0);
+        result.make_move_in_place(m);
+        result
+    }
 
+    /// Plays `m` on this board, returning the state needed by [`Board::unmake_move`]
+    /// to take it back. The move must be legal.
+    #[inline(always)]
+    pub fn make_move_in_place(&mut self, m: ChessMove) -> UndoInfo {
+        let side = self.side_to_move;
         let source_bb = BitBoard::from_square(m.source);
         let dest_bb = BitBoard::from_square(m.dest);
 
         let moved_piece = self.get_piece(m.source).unwrap();
+        let castled = moved_piece == Piece::King && self.is_castle(m);
+        let captured = if castled {
+            None
+        } else {
+            self.get_piece(m.dest)
+        };
+
+        let undo = UndoInfo {
+            captured,
+            en_passant: self.en_passant,
+            castle_rights: self.castle_rights,
+            pinned_bitboard: self.pinned_bitboard,
+            checkers_bitboard: self.checkers_bitboard,
+            hash: self.hash,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            castled,
+        };
 
-        result.xor(moved_piece, source_bb, self.side_to_move);
-        result.xor(moved_piece, dest_bb, self.side_to_move);
-        if let Some(captured) = self.get_piece(m.dest) {
-            result.xor(captured, dest_bb, !self.side_to_move);
+        if let Some(square) = self.en_passant {
+            self.hash ^= zobrist::get_en_passant_key(square.get_file());
         }
+        self.en_passant = None;
+        self.checkers_bitboard = BitBoard(0);
+        self.pinned_bitboard = BitBoard(0);
 
-        result
-            .castle_rights
-            .update_from_square(!self.side_to_move, m.dest);
-        result
-            .castle_rights
-            .update_from_square(self.side_to_move, m.source);
+        if castled {
+            self.xor_castle(side, m, undo.castle_rights);
+        } else {
+            self.xor(moved_piece, source_bb, side);
+            self.xor(moved_piece, dest_bb, side);
+        }
+        if let Some(captured) = captured {
+            self.xor(captured, dest_bb, !side);
+        }
 
-        let enemy_king =
-            self.get_piece_bitboard(Piece::King) & self.get_color_bitboard(!self.side_to_move);
-        let enemy_king_sq = enemy_king.to_square();
+        if moved_piece == Piece::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if side == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
 
-        let move_bb = source_bb ^ dest_bb;
-        let has_castled =
-            moved_piece == Piece::King && (move_bb & magic::get_castle_squares()) == move_bb;
+        self.castle_rights.update_from_square(!side, m.dest);
+        self.castle_rights.update_from_square(side, m.source);
+        self.hash ^= zobrist::get_castles_key(undo.castle_rights)
+            ^ zobrist::get_castles_key(self.castle_rights);
 
+        let enemy_king_sq = self.get_king_square(!side);
         if moved_piece == Piece::Knight {
-            result.checkers_bitboard ^= magic::get_knight_moves(enemy_king_sq) & dest_bb;
+            self.checkers_bitboard ^= magic::get_knight_moves(enemy_king_sq) & dest_bb;
         } else if moved_piece == Piece::Pawn {
-            if let Some(Piece::Knight) = m.promotion {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(Piece::Knight, dest_bb, self.side_to_move);
-                result.checkers_bitboard ^= magic::get_knight_moves(enemy_king_sq) & dest_bb;
-            } else if let Some(promotion) = m.promotion {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(promotion, dest_bb, self.side_to_move);
-            } else if !(source_bb & magic::get_pawn_source_double_moves()).is_empty()
-                && !(dest_bb & magic::get_pawn_dest_double_moves()).is_empty()
-            {
-                result.set_en_passant(m.dest.backward(self.side_to_move).unwrap());
-                result.checkers_bitboard ^=
-                    magic::get_pawn_attacks(enemy_king_sq, !self.side_to_move, dest_bb);
-            } else if Some(m.dest) == self.en_passant {
-                result.xor(
-                    Piece::Pawn,
-                    BitBoard::from_square(m.dest.forward(!self.side_to_move).unwrap()),
-                    !self.side_to_move,
-                );
-                result.checkers_bitboard ^=
-                    magic::get_pawn_attacks(enemy_king_sq, !self.side_to_move, dest_bb);
-            } else {
-                result.checkers_bitboard ^=
-                    magic::get_pawn_attacks(enemy_king_sq, !self.side_to_move, dest_bb);
-            }
-        } else if has_castled {
-            let backrank = match self.side_to_move {
-                Color::White => Rank::First,
-                Color::Black => Rank::Eighth,
-            };
-            let start_bb = BitBoard::set(
-                backrank,
-                match m.dest.get_file() {
-                    File::C | File::B => File::A,
-                    File::G => File::H,
-                    _ => unreachable!(),
-                },
-            );
-            let end_bb = BitBoard::set(
-                backrank,
-                match m.dest.get_file() {
-                    File::C | File::B => File::D,
-                    File::G => File::F,
-                    _ => unreachable!(),
-                },
+            self.finish_pawn_move(m, side, undo.en_passant, enemy_king_sq);
+        }
+        self.update_slider_checks_and_pins(side, enemy_king_sq);
+
+        self.side_to_move = !side;
+        self.hash ^= zobrist::get_side_key();
+        undo
+    }
+
+    /// Completes the pawn move `m` of `side` once the pawn stands on its destination:
+    /// promotes it, sets the en passant square after a double move or removes the pawn
+    /// taken en passant, with `en_passant` the square before the move. Also adds the
+    /// pawn, or the knight it promoted to, to the checkers of the enemy king.
+    #[inline(always)]
+    fn finish_pawn_move(
+        &mut self,
+        m: ChessMove,
+        side: Color,
+        en_passant: Option<Square>,
+        enemy_king_sq: Square,
+    ) {
+        let source_bb = BitBoard::from_square(m.source);
+        let dest_bb = BitBoard::from_square(m.dest);
+
+        if let Some(Piece::Knight) = m.promotion {
+            self.xor(Piece::Pawn, dest_bb, side);
+            self.xor(Piece::Knight, dest_bb, side);
+            self.checkers_bitboard ^= magic::get_knight_moves(enemy_king_sq) & dest_bb;
+        } else if let Some(promotion) = m.promotion {
+            self.xor(Piece::Pawn, dest_bb, side);
+            self.xor(promotion, dest_bb, side);
+        } else if !(source_bb & magic::get_pawn_source_double_moves()).is_empty()
+            && !(dest_bb & magic::get_pawn_dest_double_moves()).is_empty()
+        {
+            self.set_en_passant(m.dest.backward(side).unwrap());
+            self.checkers_bitboard ^= magic::get_pawn_attacks(enemy_king_sq, !side, dest_bb);
+        } else if Some(m.dest) == en_passant {
+            self.xor(
+                Piece::Pawn,
+                BitBoard::from_square(m.dest.forward(!side).unwrap()),
+                !side,
             );
-            result.xor(Piece::Rook, start_bb, self.side_to_move);
-            result.xor(Piece::Rook, end_bb, self.side_to_move);
+            self.checkers_bitboard ^= magic::get_pawn_attacks(enemy_king_sq, !side, dest_bb);
+        } else {
+            self.checkers_bitboard ^= magic::get_pawn_attacks(enemy_king_sq, !side, dest_bb);
         }
+    }
 
-        let rays_attackers = result.get_color_bitboard(self.side_to_move)
+    /// Adds the bishops, rooks and queens of `side` attacking the enemy king to the
+    /// checkers, and the lone pieces standing between them and the king to the pinned.
+    #[inline(always)]
+    fn update_slider_checks_and_pins(&mut self, side: Color, enemy_king_sq: Square) {
+        let rays_attackers = self.get_color_bitboard(side)
             & ((magic::get_bishop_rays(enemy_king_sq)
-                & (result.get_piece_bitboard(Piece::Bishop)
-                    | result.get_piece_bitboard(Piece::Queen)))
+                & (self.get_piece_bitboard(Piece::Bishop)
+                    | self.get_piece_bitboard(Piece::Queen)))
                 | (magic::get_rook_rays(enemy_king_sq)
-                    & (result.get_piece_bitboard(Piece::Rook)
-                        | result.get_piece_bitboard(Piece::Queen))));
+                    & (self.get_piece_bitboard(Piece::Rook)
+                        | self.get_piece_bitboard(Piece::Queen))));
 
         for square in rays_attackers.get_squares() {
-            let between = magic::get_between(square, enemy_king_sq) & result.combined_bitboard;
+            let between = magic::get_between(square, enemy_king_sq) & self.combined_bitboard;
 
             if between.is_empty() {
-                result.checkers_bitboard ^= BitBoard::from_square(square);
+                self.checkers_bitboard ^= BitBoard::from_square(square);
             } else if between.0.count_ones() == 1 {
-                result.pinned_bitboard ^= between;
+                self.pinned_bitboard ^= between;
             }
         }
-
-        result.side_to_move = !result.side_to_move;
-        result
     }
-}
 
-impl Default for Board {
-    fn default() -> Self {
-        Self::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
-    }
-}
+    /// Takes back `m`, which must be the last move played with [`Board::make_move_in_place`].
+    #[inline(always)]
+    pub fn unmake_move(&mut self, m: ChessMove, undo: UndoInfo) {
+        let side = !self.side_to_move;
+        let source_bb = BitBoard::from_square(m.source);
+        let dest_bb = BitBoard::from_square(m.dest);
 
-impl FromStr for Board {
-    type Err = Error;
+        if undo.castled {
+            self.xor_castle(side, m, undo.castle_rights);
+        } else {
+            let dest_piece = self.get_piece(m.dest).unwrap();
+            let moved_piece = if m.promotion.is_some() {
+                Piece::Pawn
+            } else {
+                dest_piece
+            };
 
-    fn from_str(fen: &str) -> Result<Self, Self::Err> {
-        let tokens: Vec<&str> = fen.split_whitespace().collect();
-        if tokens.len() < 4 {
-            bail!("invalid fen string");
-        }
-
-        let ranks = tokens[0].split('/').collect::<Vec<_>>();
-        if ranks.len() != 8 {
-            bail!("invalid fen string");
-        }
-
-        let mut board = Self::new();
-        for (rank_idx, rank_str) in ranks.iter().enumerate() {
-            let rank = Rank::from_index(7 - rank_idx); // 8th rank first
-            let mut file = File::from_index(0);
-            for c in rank_str.chars() {
-                match c {
-                    '1'..='8' => {
-                        let skip = c.to_digit(10).unwrap() as usize;
-                        file = File::from_index(file.to_index() + skip);
-                    }
-                    _ => {
-                        let color = if c.is_uppercase() {
-                            Color::White
-                        } else {
-                            Color::Black
-                        };
-
-                        let piece = match c.to_ascii_lowercase() {
-                            'k' => Piece::King,
-                            'q' => Piece::Queen,
-                            'r' => Piece::Rook,
-                            'b' => Piece::Bishop,
-                            'n' => Piece::Knight,
-                            'p' => Piece::Pawn,
-                            _ => bail!("invalid fen string"),
-                        };
-
-                        let square = Square::new(rank, file);
-                        board.place_piece(square, piece, color);
-
-                        file = file.right();
-                    }
-                }
+            self.xor(dest_piece, dest_bb, side);
+            self.xor(moved_piece, source_bb, side);
+            if let Some(captured) = undo.captured {
+                self.xor(captured, dest_bb, !side);
             }
-        }
 
-        match tokens[1] {
-            "w" => board.set_side(Color::White),
-            "b" => board.set_side(Color::Black),
-            _ => bail!("Turno inválido: {}", tokens[1]),
+            if moved_piece == Piece::Pawn && Some(m.dest) == undo.en_passant {
+                self.xor(
+                    Piece::Pawn,
+                    BitBoard::from_square(m.dest.forward(!side).unwrap()),
+                    !side,
+                );
+            }
         }
 
-        let rights = CastleRights::from_str(tokens[2])?;
-        board.set_castling_rights(rights);
+        self.side_to_move = side;
+        self.en_passant = undo.en_passant;
+        self.castle_rights = undo.castle_rights;
+        self.pinned_bitboard = undo.pinned_bitboard;
+        self.checkers_bitboard = undo.checkers_bitboard;
+        self.hash = undo.hash;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+    }
 
-        if let Ok(sq) = Square::from_str(tokens[3]) {
-            board.side_to_move = !board.side_to_move;
-            board.set_en_passant(sq);
-            board.side_to_move = !board.side_to_move;
-        }
+    /// Checks whether the king move `m` is a castling move: the king capturing its own
+    /// rook, or outside Chess960 the king moving two squares.
+    #[inline(always)]
+    pub(crate) fn is_castle(&self, m: ChessMove) -> bool {
+        !(self.get_color_bitboard(self.side_to_move) & BitBoard::from_square(m.dest)).is_empty()
+            || (!self.chess960
+                && m.source
+                    .get_file()
+                    .to_index()
+                    .abs_diff(m.dest.get_file().to_index())
+                    == 2)
+    }
 
-        board.update_attacked_bitboards();
+    /// Toggles the king and the castling rook of `color` between their squares before
+    /// and after the castling move `m`, with `rights` the castling rights before the move.
+    #[inline(always)]
+    fn xor_castle(&mut self, color: Color, m: ChessMove, rights: CastleRights) {
+        let rank = color.starting_rank();
+        let (rook, king_dest, rook_dest) =
+            if m.dest.get_file().to_index() > m.source.get_file().to_index() {
+                (rights.kingside_rook(color), File::G, File::F)
+            } else {
+                (rights.queenside_rook(color), File::C, File::D)
+            };
 
-        Ok(board)
+        self.xor(Piece::King, BitBoard::from_square(m.source), color);
+        self.xor(Piece::Rook, BitBoard::from_square(rook), color);
+        self.xor(Piece::King, BitBoard::set(rank, king_dest), color);
+        self.xor(Piece::Rook, BitBoard::set(rank, rook_dest), color);
     }
-}
 
-impl fmt::Display for Board {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for rank in ALL_RANKS.iter().rev() {
-            let mut empty = 0;
-            for file in ALL_FILES.iter() {
-                let square = Square::new(*rank, *file);
-                if let Some((piece, color)) = self.get_piece_and_color(square) {
-                    if empty != 0 {
-                        write!(f, "{}", empty)?;
-                        empty = 0;
-                    }
-                    write!(f, "{}", piece.to_string(color))?;
-                } else {
-                    empty += 1;
-                }
-            }
-            if empty != 0 {
-                write!(f, "{}", empty)?;
-            }
-            if *rank != Rank::First {
-                write!(f, "/")?;
-            }
-        }
+    /// Returns the FEN of the position with the castling rights written as Shredder-FEN,
+    /// that is with the files of the castling rooks.
+    pub fn shredder_fen(&self) -> String {
+        let mut fen = String::new();
+        self.write_fen(&mut fen, true).unwrap();
+        fen
+    }
+
+    fn write_fen(&self, f: &mut dyn fmt::Write, shredder: bool) -> fmt::Result {
+        fen::write_placement(f, |square| self.get_piece_and_color(square))?;
         write!(f, " ")?;
 
         if self.side_to_move == Color::White {
@@ -410,8 +809,12 @@ impl fmt::Display for Board {
             write!(f, "b ")?;
         }
 
-        write!(f, "{}", self.castle_rights)?;
-        write!(f, " ")?;
+        let rooks = self.get_piece_bitboard(Piece::Rook);
+        let rooks = [
+            rooks & self.get_color_bitboard(Color::White),
+            rooks & self.get_color_bitboard(Color::Black),
+        ];
+        write!(f, "{} ", self.castle_rights.to_fen_string(rooks, shredder))?;
 
         if let Some(square) = self.en_passant {
             write!(f, "{}", square)?;
@@ -419,7 +822,101 @@ impl fmt::Display for Board {
             write!(f, "-")?;
         }
 
-        write!(f, " 0 1") // TODO
+        write!(f, " {} {}", self.halfmove_clock, self.fullmove_number)
+    }
+
+    /// Returns the Chess960 starting position with the Scharnagl number `index`,
+    /// from 0 to 959. The standard starting position is number 518.
+    pub fn from_chess960_index(index: usize) -> Option<Board> {
+        if index >= 960 {
+            return None;
+        }
+
+        let mut back_rank = [None; 8];
+        let mut n = index;
+        back_rank[2 * (n % 4) + 1] = Some(Piece::Bishop);
+        n /= 4;
+        back_rank[2 * (n % 4)] = Some(Piece::Bishop);
+        n /= 4;
+
+        let mut place_on_empty = |nth: usize, piece: Piece| {
+            let file = (0..8)
+                .filter(|&file| back_rank[file].is_none())
+                .nth(nth)
+                .unwrap();
+            back_rank[file] = Some(piece);
+        };
+        place_on_empty(n % 6, Piece::Queen);
+        n /= 6;
+
+        // the knights take two of the five empty squares, in this order
+        const KNIGHTS: [(usize, usize); 10] = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ];
+        let (first, second) = KNIGHTS[n];
+        place_on_empty(second, Piece::Knight);
+        place_on_empty(first, Piece::Knight);
+        for piece in [Piece::Rook, Piece::King, Piece::Rook] {
+            place_on_empty(0, piece);
+        }
+
+        let mut builder = BoardBuilder::new();
+        let mut rooks = (0..8).filter(|&file| back_rank[file] == Some(Piece::Rook));
+        let (queenside, kingside) = (rooks.next().unwrap(), rooks.next().unwrap());
+        let king = (0..8)
+            .find(|&file| back_rank[file] == Some(Piece::King))
+            .unwrap();
+
+        let mut rights = CastleRights::default();
+        for color in [Color::White, Color::Black] {
+            for (file, piece) in back_rank.iter().enumerate() {
+                builder.piece(
+                    Square::new(color.starting_rank(), File::from_index(file)),
+                    piece.unwrap(),
+                    color,
+                );
+                builder.piece(
+                    Square::new(color.starting_rank().forward(color), File::from_index(file)),
+                    Piece::Pawn,
+                    color,
+                );
+            }
+            rights.set_kingside(color, File::from_index(king), File::from_index(kingside));
+            rights.set_queenside(color, File::from_index(king), File::from_index(queenside));
+        }
+
+        builder.castle_rights(rights).chess960(true).build().ok()
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
+    }
+}
+
+impl FromStr for Board {
+    type Err = FenError;
+
+    /// Parses a FEN string, rejecting positions that can't arise in a legal game.
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        BoardBuilder::from_str(fen)?.build()
+    }
+}
+
+impl fmt::Display for Board {
+    /// Formats the board as a FEN string, writing the castling rights as X-FEN.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_fen(f, false)
     }
 }
 
@@ -461,6 +958,415 @@ mod tests {
         assert_eq!(board_fen, initial_fen);
     }
 
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn assert_incremental_hash(board: &Board, depth: usize) {
+        assert_eq!(board.get_hash(), board.compute_hash(), "{}", board);
+        if depth == 0 {
+            return;
+        }
+        for m in MoveGen::new_legal(board) {
+            assert_incremental_hash(&board.make_move(m), depth - 1);
+        }
+    }
+
+    #[test]
+    fn test_board_incremental_hash() {
+        for (fen, depth) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                4,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                3,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                3,
+            ),
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 4),
+        ] {
+            assert_incremental_hash(&Board::from_str(fen).unwrap(), depth);
+        }
+    }
+
+    fn assert_unmake_restores(board: &mut Board, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+        let fen = format!("{}", board);
+        let (pinned, checkers, hash) = (
+            board.get_pinned_bitboard(),
+            board.get_checkers_bitboard(),
+            board.get_hash(),
+        );
+        for m in MoveGen::new_legal(board) {
+            let undo = board.make_move_in_place(m);
+            assert_eq!(
+                format!("{}", board),
+                format!("{}", Board::from_str(&fen).unwrap().make_move(m))
+            );
+            assert_unmake_restores(board, depth - 1);
+            board.unmake_move(m, undo);
+
+            assert_eq!(format!("{}", board), fen);
+            assert_eq!(board.get_pinned_bitboard(), pinned);
+            assert_eq!(board.get_checkers_bitboard(), checkers);
+            assert_eq!(board.get_hash(), hash);
+            assert_eq!(board.compute_hash(), hash);
+        }
+    }
+
+    #[test]
+    fn test_board_make_unmake_move() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1",
+            "qnb1r1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+            // the fullmove number saturates, and must still be restored
+            "4k3/8/8/8/8/8/8/R3K3 b Q - 0 65535",
+        ] {
+            assert_unmake_restores(&mut Board::from_str(fen).unwrap(), 3);
+        }
+    }
+
+    fn assert_is_legal(board: &Board, depth: usize) {
+        let legals: Vec<ChessMove> = MoveGen::new_legal(board).collect();
+        for source in Square::all_squares() {
+            for dest in Square::all_squares() {
+                for promotion in [
+                    None,
+                    Some(Piece::Queen),
+                    Some(Piece::Knight),
+                    Some(Piece::King),
+                ] {
+                    let m = ChessMove::new(source, dest, promotion);
+                    assert_eq!(
+                        board.is_legal(m),
+                        legals.contains(&m),
+                        "{:?} in {}",
+                        m,
+                        board
+                    );
+                }
+            }
+        }
+        if depth > 1 {
+            for m in legals {
+                assert_is_legal(&board.make_move(m), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_is_legal() {
+        for (fen, depth) in [
+            (START_FEN, 2),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                2,
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                2,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 2),
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                2,
+            ),
+        ] {
+            assert_is_legal(&Board::from_str(fen).unwrap(), depth);
+        }
+    }
+
+    #[test]
+    fn test_board_try_make_move() {
+        let board = Board::default();
+        let mv = |src: &str, dest: &str| {
+            ChessMove::new(src.parse().unwrap(), dest.parse().unwrap(), None)
+        };
+        assert!(board.try_make_move(mv("e2", "e4")).is_ok());
+        assert!(board.try_make_move(mv("e4", "e5")).is_err());
+        assert!(board.try_make_move(mv("e7", "e5")).is_err());
+        assert!(board.try_make_move(mv("e1", "g1")).is_err());
+    }
+
+    #[test]
+    fn test_board_null_move() {
+        let board =
+            Board::from_str("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap();
+        let null = board.null_move().unwrap();
+        assert_eq!(
+            format!("{}", null),
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 1 3"
+        );
+        assert_eq!(null.get_hash(), null.compute_hash());
+        assert_eq!(
+            null.null_move().unwrap().get_hash(),
+            Board::from_str("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 2 4")
+                .unwrap()
+                .get_hash()
+        );
+
+        // pins and checkers are computed for the new side to move
+        let board = Board::from_str("4k3/4n3/8/8/8/8/8/4R1K1 w - - 0 1").unwrap();
+        assert!(board.get_pinned_bitboard().is_empty());
+        let null = board.null_move().unwrap();
+        assert_eq!(
+            null.get_pinned_bitboard(),
+            BitBoard::from_square(Square::from_str("e7").unwrap())
+        );
+        assert!(MoveGen::new_legal(&null).all(|m| m.source != Square::from_str("e7").unwrap()));
+
+        let board = Board::from_str("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").unwrap();
+        assert!(board.null_move().is_none());
+    }
+
+    #[test]
+    fn test_board_hash_transposition() {
+        let moves = |board: &Board, moves: &[(&str, &str)]| {
+            moves.iter().fold(board.clone(), |b, (src, dest)| {
+                b.make_move(ChessMove::new(
+                    src.parse().unwrap(),
+                    dest.parse().unwrap(),
+                    None,
+                ))
+            })
+        };
+        let board = Board::default();
+        let a = moves(&board, &[("g1", "f3"), ("g8", "f6"), ("b1", "c3")]);
+        let b = moves(&board, &[("b1", "c3"), ("g8", "f6"), ("g1", "f3")]);
+        assert_eq!(a.get_hash(), b.get_hash());
+
+        let c = moves(
+            &board,
+            &[("e2", "e4"), ("d7", "d5"), ("e4", "e5"), ("f7", "f5")],
+        );
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(c.get_hash(), Board::from_str(fen).unwrap().get_hash());
+
+        // same pieces, no en passant square
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3";
+        assert_ne!(c.get_hash(), Board::from_str(fen).unwrap().get_hash());
+    }
+
+    #[test]
+    fn test_board_move_counters() {
+        let fen = "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+        let board = Board::from_str(fen).unwrap();
+        assert_eq!(board.halfmove_clock(), 1);
+        assert_eq!(board.fullmove_number(), 2);
+        assert_eq!(format!("{}", board), fen);
+
+        let mv = |src: &str, dest: &str| {
+            ChessMove::new(src.parse().unwrap(), dest.parse().unwrap(), None)
+        };
+
+        let board = board.make_move(mv("b8", "c6"));
+        assert_eq!(board.halfmove_clock(), 2);
+        assert_eq!(board.fullmove_number(), 3);
+
+        let board = board.make_move(mv("f1", "b5"));
+        assert_eq!(board.halfmove_clock(), 3);
+        assert_eq!(board.fullmove_number(), 3);
+
+        let board = board.make_move(mv("d7", "d6"));
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 4);
+
+        let board = board.make_move(mv("b5", "c6"));
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(
+            format!("{}", board),
+            "r1bqkbnr/pp2pppp/2Bp4/2p5/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 4"
+        );
+
+        // the counters are optional
+        let board = Board::from_str("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+        assert!(Board::from_str("8/8/8/8/8/8/8/K6k w - - x 1").is_err());
+    }
+
+    #[test]
+    fn test_board_status() {
+        let status = |fen: &str| Board::from_str(fen).unwrap().status();
+        assert_eq!(status(START_FEN), BoardStatus::Ongoing);
+        // fool's mate
+        assert_eq!(
+            status("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            BoardStatus::Checkmate
+        );
+        assert_eq!(
+            status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            BoardStatus::Stalemate
+        );
+        // in check, but can escape
+        assert_eq!(
+            status("7k/8/6K1/8/8/8/8/7Q b - - 0 1"),
+            BoardStatus::Ongoing
+        );
+    }
+
+    #[test]
+    fn test_board_insufficient_material() {
+        let insufficient = |fen: &str| Board::from_str(fen).unwrap().is_insufficient_material();
+        assert!(!insufficient(START_FEN));
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/8 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/8/8/3K4/5N2/8 w - - 0 1"));
+        assert!(insufficient("8/8/4kb2/8/8/3K4/8/8 w - - 0 1"));
+        // bishops on the same color
+        assert!(insufficient("8/8/4k3/2b5/8/3K4/8/6B1 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/2b5/3B4/3K4/8/6B1 w - - 0 1"));
+        // bishops on different colors
+        assert!(!insufficient("8/8/4k3/2b5/8/3K4/8/5B2 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/5NN1/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/5N2/1n6 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/5P2/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3K4/5R2/8 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_board_fen_errors() {
+        let error = |fen: &str| Board::from_str(fen).unwrap_err();
+        assert_eq!(error("8/8/8/8/8/8/8/K6k w"), FenError::InvalidFieldCount(2));
+        assert_eq!(
+            error("8/8/8/8/8/8/K6k w - - 0 1"),
+            FenError::InvalidRankCount(7)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K5xk w - - 0 1"),
+            FenError::InvalidPiece {
+                rank: Rank::First,
+                character: 'x'
+            }
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K7k w - - 0 1"),
+            FenError::InvalidRankLength(Rank::First)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K5k w - - 0 1"),
+            FenError::InvalidRankLength(Rank::First)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k x - - 0 1"),
+            FenError::InvalidSideToMove("x".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w X - 0 1"),
+            FenError::InvalidCastleRights('X')
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - e9 0 1"),
+            FenError::InvalidSquare("e9".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - é 0 1"),
+            FenError::InvalidSquare("é".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - x 1"),
+            FenError::InvalidHalfmoveClock("x".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - 0 -1"),
+            FenError::InvalidFullmoveNumber("-1".to_string())
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K6k w - - 0 0"),
+            FenError::InvalidFullmoveNumber("0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_board_illegal_positions() {
+        let error = |fen: &str| Board::from_str(fen).expect_err(fen);
+        assert_eq!(
+            error("8/8/8/8/8/8/8/K7 w - - 0 1"),
+            FenError::MissingKing(Color::Black)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/KK5k w - - 0 1"),
+            FenError::TooManyKings(Color::White)
+        );
+        assert_eq!(
+            error("8/8/8/8/8/8/8/KP5k w - - 0 1"),
+            FenError::PawnOnBackRank(Square::from_str("b1").unwrap())
+        );
+        assert_eq!(
+            error("p7/8/8/8/8/8/8/K6k w - - 0 1"),
+            FenError::PawnOnBackRank(Square::from_str("a8").unwrap())
+        );
+        assert_eq!(
+            error("QQQQQQQQ/QQQQQQQQ/QQk5/8/8/8/8/K7 w - - 0 1"),
+            FenError::TooManyPieces(Color::White)
+        );
+        assert_eq!(
+            error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1"),
+            FenError::CastleRightsMismatch('K')
+        );
+        assert_eq!(
+            error("rnbqkbnr/pppppppp/8/8/8/4K3/PPPPPPPP/RNBQ1BNR w KQkq - 0 1"),
+            FenError::CastleRightsMismatch('K')
+        );
+        assert_eq!(
+            error("1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::CastleRightsMismatch('q')
+        );
+        assert_eq!(
+            error("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1"),
+            FenError::InvalidEnPassant(Square::from_str("e3").unwrap())
+        );
+        assert_eq!(
+            error("rnbqkbnr/pppppppp/8/8/8/4P3/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            FenError::InvalidEnPassant(Square::from_str("e3").unwrap())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            FenError::OpponentInCheck
+        );
+    }
+
+    #[test]
+    fn test_board_from_str_lenient() {
+        // the move generator can't handle these
+        assert_eq!(
+            Board::from_str_lenient("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1").err(),
+            Some(FenError::OpponentInCheck)
+        );
+        assert_eq!(
+            Board::from_str_lenient("k7/8/NNNNNNNN/NNNNNNNN/NNNNNNNN/NNNNNNNN/N7/K7 w - - 0 1")
+                .err(),
+            Some(FenError::TooManyPieces(Color::White))
+        );
+
+        let board = Board::from_str_lenient("4k3/8/8/8/8/8/PPPPPPPP/1P2K3 w - - 0 1").unwrap();
+        assert_eq!(MoveGen::perft_test(&board, 2), 18 * 5);
+
+        let board = Board::from_str_lenient("8/8/8/8/8/8/8/K6k w - - 0 0").unwrap();
+        assert_eq!(board.fullmove_number(), 0);
+
+        let board = Board::from_str_lenient("8/8/8/8/8/8/8/KP5k w K e6 0 1").unwrap();
+        assert!(board.castle_rights().is_empty());
+        assert_eq!(board.en_passant(), None);
+        assert_eq!(format!("{}", board), "8/8/8/8/8/8/8/KP5k w - - 0 1");
+
+        assert_eq!(
+            Board::from_str_lenient("8/8/8/8/8/8/8/K7 w - - 0 1").err(),
+            Some(FenError::MissingKing(Color::Black))
+        );
+        assert!(Board::from_str_lenient("P7/8/8/8/8/8/8/K6k w - - 0 1").is_err());
+    }
+
     #[test]
     fn test_board_from_str() {
         assert!(
@@ -471,4 +1377,121 @@ mod tests {
                 .is_ok()
         );
     }
+
+    #[test]
+    fn test_board_chess960_fen() {
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let board = Board::from_str(fen).unwrap();
+        assert!(board.is_chess960());
+        assert_eq!(board.shredder_fen(), fen);
+        assert_eq!(
+            format!("{}", board),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+
+        // X-FEN uses the file letter when another rook stands between the castling rook
+        // and the corner
+        let board = Board::from_str("rk2r3/8/8/8/8/8/8/RK2R2R w EAea - 0 1").unwrap();
+        assert!(board.is_chess960());
+        assert_eq!(
+            format!("{}", board),
+            "rk2r3/8/8/8/8/8/8/RK2R2R w EQkq - 0 1"
+        );
+        assert_eq!(
+            board.shredder_fen(),
+            "rk2r3/8/8/8/8/8/8/RK2R2R w EAea - 0 1"
+        );
+        assert_eq!(
+            Board::from_str(&format!("{}", board))
+                .unwrap()
+                .castle_rights(),
+            board.castle_rights()
+        );
+
+        let board = Board::default();
+        assert!(!board.is_chess960());
+        assert_eq!(
+            board.shredder_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+        );
+        assert!(Board::from_str("4k3/8/8/8/8/8/8/4K3 w E - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_board_chess960_castling() {
+        let board =
+            Board::from_str("1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1").unwrap();
+        let mv = |m: &str| ChessMove::new(m[0..2].parse().unwrap(), m[2..4].parse().unwrap(), None);
+
+        assert!(board.is_legal(mv("e1g1")));
+        assert!(board.is_legal(mv("e1b1")));
+        assert!(!board.is_legal(mv("e1c1")));
+        let board = board.make_move(mv("e1g1"));
+        assert_eq!(
+            format!("{}", board),
+            "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R3RK1 b kq - 1 1"
+        );
+        let board = board.make_move(mv("e8b8"));
+        assert_eq!(
+            format!("{}", board),
+            "2kr2r1/pppppppp/8/8/8/8/PPPPPPPP/1R3RK1 w - - 2 2"
+        );
+        assert_eq!(board.get_hash(), board.compute_hash());
+
+        // the castling rook doesn't shield the king's path from attacks
+        let board = Board::from_str("4k3/8/8/8/8/8/8/r1RK4 w C - 0 1").unwrap();
+        assert!(!board.is_legal(mv("d1c1")));
+        assert!(MoveGen::new_legal(&board).all(|m| m.dest != Square::from_str("c1").unwrap()));
+    }
+
+    #[test]
+    fn test_board_from_chess960_index() {
+        assert_eq!(
+            format!("{}", Board::from_chess960_index(518).unwrap()),
+            START_FEN
+        );
+        assert_eq!(
+            Board::from_chess960_index(0).unwrap().shredder_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+        );
+        assert_eq!(
+            Board::from_chess960_index(959).unwrap().shredder_fen(),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1"
+        );
+        assert!(Board::from_chess960_index(960).is_none());
+
+        let mut positions: Vec<String> = (0..960)
+            .map(|index| format!("{}", Board::from_chess960_index(index).unwrap()))
+            .collect();
+        positions.sort();
+        positions.dedup();
+        assert_eq!(positions.len(), 960);
+    }
+
+    #[test]
+    fn test_board_parse_uci_move() {
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        for m in MoveGen::new_legal(&board) {
+            assert_eq!(board.parse_uci_move(&m.to_string()).unwrap(), m);
+        }
+        assert_eq!(board.parse_uci_move("e1h1").unwrap().to_string(), "e1g1");
+        assert_eq!(board.parse_uci_move("e1a1").unwrap().to_string(), "e1c1");
+        assert!(board.parse_uci_move("e1b1").is_err());
+        assert!(board.parse_uci_move("a1a3").is_err());
+        assert!(board.parse_uci_move("e2e4").is_err());
+        assert!(board.parse_uci_move("xyz").is_err());
+
+        let board =
+            Board::from_str("1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1").unwrap();
+        assert_eq!(board.parse_uci_move("e1g1").unwrap().to_string(), "e1g1");
+        assert_eq!(board.parse_uci_move("e1b1").unwrap().to_string(), "e1b1");
+        assert_eq!(board.parse_uci_move("e1c1").unwrap().to_string(), "e1b1");
+        assert_eq!(board.parse_uci_move("e1f1").unwrap().to_string(), "e1f1");
+
+        let board = Board::from_str("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(board.parse_uci_move("a7a8").is_err());
+        assert!(board.is_legal(board.parse_uci_move("a7a8n").unwrap()));
+    }
 }
@@ -0,0 +1,437 @@
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::castle_rights::CastleRights;
+use crate::color::Color;
+use crate::fen::{self, FenError};
+use crate::file::{ALL_FILES, File};
+use crate::pieces::Piece;
+use crate::rank::Rank;
+use crate::square::Square;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A mutable description of a position, used to set up a `Board` piece by piece.
+///
+/// Nothing is checked until [`BoardBuilder::build`], which validates the position
+/// the same way as `Board::from_str` and computes the pins, checkers and hash.
+#[derive(Clone)]
+pub struct BoardBuilder {
+    pieces: [Option<(Piece, Color)>; 64],
+    side_to_move: Color,
+    castle_rights: CastleRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    chess960: bool,
+}
+
+impl BoardBuilder {
+    /// Creates a builder for an empty board with White to move.
+    #[inline(always)]
+    pub fn new() -> Self {
+        Self {
+            pieces: [None; 64],
+            side_to_move: Color::White,
+            castle_rights: CastleRights::default(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
+        }
+    }
+
+    /// Puts a piece on `square`, replacing whatever was there.
+    #[inline(always)]
+    pub fn piece(&mut self, square: Square, piece: Piece, color: Color) -> &mut Self {
+        self.pieces[square.to_index()] = Some((piece, color));
+        self
+    }
+
+    #[inline(always)]
+    pub fn clear_square(&mut self, square: Square) -> &mut Self {
+        self.pieces[square.to_index()] = None;
+        self
+    }
+
+    #[inline(always)]
+    pub fn side_to_move(&mut self, color: Color) -> &mut Self {
+        self.side_to_move = color;
+        self
+    }
+
+    #[inline(always)]
+    pub fn castle_rights(&mut self, rights: CastleRights) -> &mut Self {
+        self.castle_rights = rights;
+        self
+    }
+
+    /// Sets the square a pawn skipped over with its double move, as written in a FEN.
+    #[inline(always)]
+    pub fn en_passant(&mut self, square: Option<Square>) -> &mut Self {
+        self.en_passant = square;
+        self
+    }
+
+    #[inline(always)]
+    pub fn halfmove_clock(&mut self, halfmove_clock: u16) -> &mut Self {
+        self.halfmove_clock = halfmove_clock;
+        self
+    }
+
+    #[inline(always)]
+    pub fn fullmove_number(&mut self, fullmove_number: u16) -> &mut Self {
+        self.fullmove_number = fullmove_number;
+        self
+    }
+
+    /// Encodes castling as the king capturing its own rook, allowing the king and
+    /// the rooks to start from any file.
+    #[inline(always)]
+    pub fn chess960(&mut self, chess960: bool) -> &mut Self {
+        self.chess960 = chess960;
+        self
+    }
+
+    #[inline(always)]
+    pub fn get_piece(&self, square: Square) -> Option<(Piece, Color)> {
+        self.pieces[square.to_index()]
+    }
+
+    /// Builds the board, rejecting positions that can't arise in a legal game.
+    pub fn build(&self) -> Result<Board, FenError> {
+        self.build_with(true)
+    }
+
+    /// Builds the board with the same relaxed checks as [`Board::from_str_lenient`].
+    pub fn build_lenient(&self) -> Result<Board, FenError> {
+        self.build_with(false)
+    }
+
+    /// Parses the castling field of a FEN, which must come after the piece placement.
+    ///
+    /// Both X-FEN and Shredder-FEN are accepted: `K` and `Q` refer to the outermost rook
+    /// on each side of the king, while a file letter names the castling rook directly.
+    /// Castling rights written with file letters, or with the king or the rook away from
+    /// their standard squares, turn on Chess960 mode.
+    fn parse_castle_rights(&mut self, field: &str) -> Result<(), FenError> {
+        let mut rights = CastleRights::default();
+        if field == "-" {
+            self.castle_rights(rights);
+            return Ok(());
+        }
+
+        let mut shredder = false;
+        for c in field.chars() {
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let rank = color.starting_rank();
+            let is_rook = |file: &File| {
+                self.get_piece(Square::new(rank, *file)) == Some((Piece::Rook, color))
+            };
+            // positions without a king on its back rank are rejected later on
+            let king_file = ALL_FILES
+                .into_iter()
+                .find(|file| self.get_piece(Square::new(rank, *file)) == Some((Piece::King, color)))
+                .unwrap_or(File::E);
+            let king_idx = king_file.to_index();
+
+            let (kingside, rook_file) = match c.to_ascii_uppercase() {
+                'K' => (
+                    true,
+                    ALL_FILES[king_idx + 1..]
+                        .iter()
+                        .rev()
+                        .find(|file| is_rook(file))
+                        .copied()
+                        .unwrap_or(File::H),
+                ),
+                'Q' => (
+                    false,
+                    ALL_FILES[..king_idx]
+                        .iter()
+                        .find(|file| is_rook(file))
+                        .copied()
+                        .unwrap_or(File::A),
+                ),
+                'A'..='H' => {
+                    shredder = true;
+                    let file = File::from_index((c.to_ascii_uppercase() as u8 - b'A') as usize);
+                    if file == king_file {
+                        return Err(FenError::InvalidCastleRights(c));
+                    }
+                    (file.to_index() > king_idx, file)
+                }
+                _ => return Err(FenError::InvalidCastleRights(c)),
+            };
+
+            if kingside {
+                if rights.has_kingside(color) {
+                    return Err(FenError::InvalidCastleRights(c));
+                }
+                rights.set_kingside(color, king_file, rook_file);
+            } else {
+                if rights.has_queenside(color) {
+                    return Err(FenError::InvalidCastleRights(c));
+                }
+                rights.set_queenside(color, king_file, rook_file);
+            }
+        }
+
+        let standard = [Color::White, Color::Black].into_iter().all(|color| {
+            rights.king_file(color) == File::E
+                && rights.kingside_rook_file(color) == File::H
+                && rights.queenside_rook_file(color) == File::A
+        });
+        self.castle_rights(rights).chess960(shredder || !standard);
+        Ok(())
+    }
+
+    fn build_with(&self, strict: bool) -> Result<Board, FenError> {
+        let mut board = Board::new();
+        for square in Square::all_squares() {
+            if let Some((piece, color)) = self.get_piece(square) {
+                board.place_piece(square, piece, color);
+            }
+        }
+        board.set_side(self.side_to_move);
+        board.set_castling_rights(self.castle_rights);
+        board.set_chess960(self.chess960);
+        board.set_move_counters(self.halfmove_clock, self.fullmove_number);
+        board.validate(self.en_passant, strict)?;
+        Ok(board)
+    }
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&Board> for BoardBuilder {
+    fn from(board: &Board) -> Self {
+        let mut builder = BoardBuilder::new();
+        for square in board.get_combined_bitboard().get_squares() {
+            let (piece, color) = board.get_piece_and_color(square).unwrap();
+            builder.piece(square, piece, color);
+        }
+        builder
+            .side_to_move(board.side_to_move())
+            .castle_rights(board.castle_rights())
+            .en_passant(board.en_passant())
+            .halfmove_clock(board.halfmove_clock())
+            .fullmove_number(board.fullmove_number())
+            .chess960(board.is_chess960());
+        builder
+    }
+}
+
+impl From<Board> for BoardBuilder {
+    fn from(board: Board) -> Self {
+        Self::from(&board)
+    }
+}
+
+impl FromStr for BoardBuilder {
+    type Err = FenError;
+
+    /// Parses the fields of a FEN string, without checking the position itself.
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = fen.split_whitespace().collect();
+        if !(4..=6).contains(&tokens.len()) {
+            return Err(FenError::InvalidFieldCount(tokens.len()));
+        }
+
+        let ranks = tokens[0].split('/').collect::<Vec<_>>();
+        if ranks.len() != 8 {
+            return Err(FenError::InvalidRankCount(ranks.len()));
+        }
+
+        let mut builder = Self::new();
+        for (rank_idx, rank_str) in ranks.iter().enumerate() {
+            let rank = Rank::from_index(7 - rank_idx); // 8th rank first
+            let mut file_idx = 0;
+            for c in rank_str.chars() {
+                match c {
+                    '1'..='8' => file_idx += c.to_digit(10).unwrap() as usize,
+                    _ => {
+                        let color = if c.is_uppercase() {
+                            Color::White
+                        } else {
+                            Color::Black
+                        };
+
+                        let piece = match c.to_ascii_lowercase() {
+                            'k' => Piece::King,
+                            'q' => Piece::Queen,
+                            'r' => Piece::Rook,
+                            'b' => Piece::Bishop,
+                            'n' => Piece::Knight,
+                            'p' => Piece::Pawn,
+                            _ => return Err(FenError::InvalidPiece { rank, character: c }),
+                        };
+
+                        if file_idx >= 8 {
+                            return Err(FenError::InvalidRankLength(rank));
+                        }
+                        builder.piece(Square::new(rank, File::from_index(file_idx)), piece, color);
+
+                        file_idx += 1;
+                    }
+                }
+            }
+            if file_idx != 8 {
+                return Err(FenError::InvalidRankLength(rank));
+            }
+        }
+
+        match tokens[1] {
+            "w" => builder.side_to_move(Color::White),
+            "b" => builder.side_to_move(Color::Black),
+            _ => return Err(FenError::InvalidSideToMove(tokens[1].to_string())),
+        };
+
+        builder.parse_castle_rights(tokens[2])?;
+
+        match tokens[3] {
+            "-" => builder.en_passant(None),
+            token => builder.en_passant(Some(Square::from_str(token)?)),
+        };
+
+        if let Some(token) = tokens.get(4) {
+            builder.halfmove_clock(
+                token
+                    .parse()
+                    .map_err(|_| FenError::InvalidHalfmoveClock(token.to_string()))?,
+            );
+        }
+
+        if let Some(token) = tokens.get(5) {
+            builder.fullmove_number(
+                token
+                    .parse()
+                    .map_err(|_| FenError::InvalidFullmoveNumber(token.to_string()))?,
+            );
+        }
+
+        Ok(builder)
+    }
+}
+
+impl fmt::Display for BoardBuilder {
+    /// Formats the builder as a FEN string, even if it doesn't describe a valid position.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fen::write_placement(f, |square| self.get_piece(square))?;
+
+        let mut rooks = [BitBoard(0); 2];
+        for square in Square::all_squares() {
+            if let Some((Piece::Rook, color)) = self.get_piece(square) {
+                rooks[color.to_index()] |= BitBoard::from_square(square);
+            }
+        }
+
+        write!(
+            f,
+            " {} {} ",
+            if self.side_to_move == Color::White {
+                "w"
+            } else {
+                "b"
+            },
+            self.castle_rights.to_fen_string(rooks, false)
+        )?;
+
+        if let Some(square) = self.en_passant {
+            write!(f, "{}", square)?;
+        } else {
+            write!(f, "-")?;
+        }
+
+        write!(f, " {} {}", self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_builder_build() {
+        let board = BoardBuilder::new()
+            .piece(Square::from_str("e1").unwrap(), Piece::King, Color::White)
+            .piece(Square::from_str("h1").unwrap(), Piece::Rook, Color::White)
+            .piece(Square::from_str("e8").unwrap(), Piece::King, Color::Black)
+            .piece(Square::from_str("e7").unwrap(), Piece::Pawn, Color::Black)
+            .side_to_move(Color::Black)
+            .castle_rights(CastleRights::from_str("K").unwrap())
+            .halfmove_clock(3)
+            .fullmove_number(20)
+            .build()
+            .unwrap();
+        assert_eq!(format!("{}", board), "4k3/4p3/8/8/8/8/8/4K2R b K - 3 20");
+        assert_eq!(
+            board.get_hash(),
+            Board::from_str(&board.to_string()).unwrap().get_hash()
+        );
+    }
+
+    #[test]
+    fn test_board_builder_checkers() {
+        let board = BoardBuilder::new()
+            .piece(Square::from_str("e1").unwrap(), Piece::King, Color::White)
+            .piece(Square::from_str("e8").unwrap(), Piece::King, Color::Black)
+            .piece(Square::from_str("e4").unwrap(), Piece::Rook, Color::White)
+            .piece(Square::from_str("e6").unwrap(), Piece::Knight, Color::Black)
+            .side_to_move(Color::Black)
+            .build()
+            .unwrap();
+        assert!(board.get_checkers_bitboard().is_empty());
+        assert_eq!(
+            board.get_pinned_bitboard().to_square(),
+            Square::from_str("e6").unwrap()
+        );
+
+        let mut builder = BoardBuilder::from(&board);
+        builder.clear_square(Square::from_str("e6").unwrap());
+        let board = builder.build().unwrap();
+        assert_eq!(
+            board.get_checkers_bitboard().to_square(),
+            Square::from_str("e4").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_board_builder_validation() {
+        let mut builder = BoardBuilder::new();
+        builder.piece(Square::from_str("e1").unwrap(), Piece::King, Color::White);
+        assert_eq!(
+            builder.build().err(),
+            Some(FenError::MissingKing(Color::Black))
+        );
+
+        builder
+            .piece(Square::from_str("e8").unwrap(), Piece::King, Color::Black)
+            .castle_rights(CastleRights::from_str("Q").unwrap());
+        assert_eq!(
+            builder.build().err(),
+            Some(FenError::CastleRightsMismatch('Q'))
+        );
+        assert_eq!(
+            builder.build_lenient().unwrap().castle_rights(),
+            CastleRights::default()
+        );
+    }
+
+    #[test]
+    fn test_board_builder_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 4 11";
+        let builder = BoardBuilder::from_str(fen).unwrap();
+        assert_eq!(format!("{}", builder), fen);
+        let board = builder.build().unwrap();
+        assert_eq!(format!("{}", BoardBuilder::from(board)), fen);
+    }
+}
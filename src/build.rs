@@ -7,6 +7,7 @@ use std::path::Path;
 
 mod bitboard;
 mod color;
+mod fen;
 mod file;
 mod gen_files;
 mod pieces;
@@ -32,5 +33,10 @@ fn main() -> Result<()> {
     write_pawn_attacks(&mut f)?;
     write_chessboard_utils(&mut f)?;
 
+    let zobrist_path = Path::new(&out_dir).join("zobrist_file.rs");
+    let mut f = File::create(&zobrist_path).unwrap();
+
+    write_zobrist(&mut f)?;
+
     Ok(())
 }
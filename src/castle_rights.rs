@@ -1,20 +1,39 @@
-use anyhow::{Error, bail};
 use std::fmt;
 use std::str::FromStr;
 
 use crate::bitboard::BitBoard;
 use crate::color::Color;
-use crate::magic::{KINGSIDE_CASTLE_SQUARES, QUEENSIDE_CASTLE_SQUARES};
+use crate::fen::FenError;
+use crate::file::File;
+use crate::magic;
 use crate::square::Square;
 
 /// A struct representing the castling rights for both players in chess.
-/// It tracks whether each player has kingside and queenside castling rights.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// It tracks whether each player has kingside and queenside castling rights,
+/// and the files the king and the castling rooks start from, which only differ
+/// from e, h and a in Chess960.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CastleRights {
     pub white_kingside: bool,
     pub white_queenside: bool,
     pub black_kingside: bool,
     pub black_queenside: bool,
+    king_files: [File; 2],
+    rook_files: [[File; 2]; 2], // [kingside, queenside] for each color
+}
+
+impl Default for CastleRights {
+    /// Returns no castling rights, with the standard king and rook files.
+    fn default() -> Self {
+        Self {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+            king_files: [File::E; 2],
+            rook_files: [[File::H, File::A]; 2],
+        }
+    }
 }
 
 impl CastleRights {
@@ -34,40 +53,195 @@ impl CastleRights {
         }
     }
 
+    /// Returns true if neither side can castle.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        !(self.white_kingside
+            || self.white_queenside
+            || self.black_kingside
+            || self.black_queenside)
+    }
+
+    #[inline(always)]
+    pub fn remove_kingside(&mut self, color: Color) {
+        match color {
+            Color::White => self.white_kingside = false,
+            Color::Black => self.black_kingside = false,
+        }
+    }
+
+    #[inline(always)]
+    pub fn remove_queenside(&mut self, color: Color) {
+        match color {
+            Color::White => self.white_queenside = false,
+            Color::Black => self.black_queenside = false,
+        }
+    }
+
+    /// Grants the kingside castling right to `color`, with the king and the rook
+    /// starting from the given files.
+    #[inline(always)]
+    pub fn set_kingside(&mut self, color: Color, king_file: File, rook_file: File) {
+        match color {
+            Color::White => self.white_kingside = true,
+            Color::Black => self.black_kingside = true,
+        }
+        self.king_files[color.to_index()] = king_file;
+        self.rook_files[color.to_index()][0] = rook_file;
+    }
+
+    /// Grants the queenside castling right to `color`, with the king and the rook
+    /// starting from the given files.
+    #[inline(always)]
+    pub fn set_queenside(&mut self, color: Color, king_file: File, rook_file: File) {
+        match color {
+            Color::White => self.white_queenside = true,
+            Color::Black => self.black_queenside = true,
+        }
+        self.king_files[color.to_index()] = king_file;
+        self.rook_files[color.to_index()][1] = rook_file;
+    }
+
+    #[inline(always)]
+    pub fn king_file(&self, color: Color) -> File {
+        self.king_files[color.to_index()]
+    }
+
+    #[inline(always)]
+    pub fn kingside_rook_file(&self, color: Color) -> File {
+        self.rook_files[color.to_index()][0]
+    }
+
+    #[inline(always)]
+    pub fn queenside_rook_file(&self, color: Color) -> File {
+        self.rook_files[color.to_index()][1]
+    }
+
+    #[inline(always)]
+    pub fn king_square(&self, color: Color) -> Square {
+        Square::new(color.starting_rank(), self.king_file(color))
+    }
+
+    #[inline(always)]
+    pub fn kingside_rook(&self, color: Color) -> Square {
+        Square::new(color.starting_rank(), self.kingside_rook_file(color))
+    }
+
+    #[inline(always)]
+    pub fn queenside_rook(&self, color: Color) -> Square {
+        Square::new(color.starting_rank(), self.queenside_rook_file(color))
+    }
+
+    /// Returns the squares that must be empty, apart from the king and the rook
+    /// themselves, to castle kingside.
     #[inline(always)]
     pub fn kingside_squares(&self, color: Color) -> BitBoard {
-        unsafe { *KINGSIDE_CASTLE_SQUARES.get_unchecked(color.to_index()) }
+        self.castle_squares(color, self.kingside_rook(color), File::G, File::F)
     }
 
+    /// Returns the squares that must be empty, apart from the king and the rook
+    /// themselves, to castle queenside.
     #[inline(always)]
     pub fn queenside_squares(&self, color: Color) -> BitBoard {
-        unsafe { *QUEENSIDE_CASTLE_SQUARES.get_unchecked(color.to_index()) }
+        self.castle_squares(color, self.queenside_rook(color), File::C, File::D)
     }
 
+    #[inline(always)]
+    fn castle_squares(
+        &self,
+        color: Color,
+        rook: Square,
+        king_dest: File,
+        rook_dest: File,
+    ) -> BitBoard {
+        let king = self.king_square(color);
+        let king_dest = Square::new(color.starting_rank(), king_dest);
+        let rook_dest = Square::new(color.starting_rank(), rook_dest);
+
+        (magic::get_between(king, king_dest)
+            | BitBoard::from_square(king_dest)
+            | magic::get_between(rook, rook_dest)
+            | BitBoard::from_square(rook_dest))
+            & !BitBoard::from_square(king)
+            & !BitBoard::from_square(rook)
+    }
+
+    /// Removes the rights lost by a king or a rook of `color` moving from,
+    /// or being captured on, `square`.
     #[inline(always)]
     pub fn update_from_square(&mut self, color: Color, square: Square) {
-        match (color, square.to_index()) {
-            (Color::White, 0) => self.white_queenside = false, // a1
-            (Color::White, 4) => {
-                self.white_queenside = false;
-                self.white_kingside = false;
-            } // e1
-            (Color::White, 7) => self.white_kingside = false,  // h1
-            (Color::Black, 56) => self.black_queenside = false, // a8
-            (Color::Black, 60) => {
-                self.black_queenside = false;
-                self.black_kingside = false;
-            } // e8
-            (Color::Black, 63) => self.black_kingside = false, // h8
-            _ => (),
+        if square.get_rank() != color.starting_rank() {
+            return;
+        }
+
+        let file = square.get_file();
+        if file == self.king_file(color) {
+            self.remove_kingside(color);
+            self.remove_queenside(color);
+        } else if file == self.kingside_rook_file(color) {
+            self.remove_kingside(color);
+        } else if file == self.queenside_rook_file(color) {
+            self.remove_queenside(color);
+        }
+    }
+
+    /// Formats the rights as the castling field of a FEN.
+    ///
+    /// `rooks` are the rooks of each color. The rights are written as X-FEN, that is
+    /// `KQkq` unless there is another rook between the castling rook and the corner,
+    /// in which case the file of the castling rook is used. With `shredder` set,
+    /// files are always used, as in Shredder-FEN.
+    pub fn to_fen_string(&self, rooks: [BitBoard; 2], shredder: bool) -> String {
+        if self.is_empty() {
+            return "-".to_string();
+        }
+
+        let mut result = String::new();
+        for color in [Color::White, Color::Black] {
+            let rank_rooks =
+                rooks[color.to_index()] & magic::get_rank_bitboard(color.starting_rank());
+            let corner_rooks = |rook: Square, corner: File| {
+                let corner = Square::new(color.starting_rank(), corner);
+                (magic::get_between(rook, corner) | BitBoard::from_square(corner))
+                    & !BitBoard::from_square(rook)
+            };
+
+            for (has_right, rook, corner, symbol) in [
+                (
+                    self.has_kingside(color),
+                    self.kingside_rook(color),
+                    File::H,
+                    'K',
+                ),
+                (
+                    self.has_queenside(color),
+                    self.queenside_rook(color),
+                    File::A,
+                    'Q',
+                ),
+            ] {
+                if !has_right {
+                    continue;
+                }
+                let c = if shredder || !(rank_rooks & corner_rooks(rook, corner)).is_empty() {
+                    (b'A' + rook.get_file().to_index() as u8) as char
+                } else {
+                    symbol
+                };
+                result.push(match color {
+                    Color::White => c,
+                    Color::Black => c.to_ascii_lowercase(),
+                });
+            }
         }
+        result
     }
 }
 
 impl fmt::Display for CastleRights {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut result = String::new();
-        if *self == CastleRights::default() {
+        if self.is_empty() {
             result = "-".to_string();
         } else {
             if self.white_kingside {
@@ -89,25 +263,27 @@ impl fmt::Display for CastleRights {
 }
 
 impl FromStr for CastleRights {
-    type Err = Error;
+    type Err = FenError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut rights = Self::default();
 
-        match s {
-            "-" => return Ok(rights),
-            s if s.len() > 4 => bail!("error"),
-            _ => {}
+        if s == "-" {
+            return Ok(rights);
         }
 
         for c in s.chars() {
-            match c {
-                'K' => rights.white_kingside = true,
-                'Q' => rights.white_queenside = true,
-                'k' => rights.black_kingside = true,
-                'q' => rights.black_queenside = true,
-                _ => bail!("error"),
+            let right = match c {
+                'K' => &mut rights.white_kingside,
+                'Q' => &mut rights.white_queenside,
+                'k' => &mut rights.black_kingside,
+                'q' => &mut rights.black_queenside,
+                _ => return Err(FenError::InvalidCastleRights(c)),
+            };
+            if *right {
+                return Err(FenError::InvalidCastleRights(c));
             }
+            *right = true;
         }
 
         Ok(rights)
@@ -126,7 +302,14 @@ mod tests {
         rights.white_queenside = true;
         rights.black_kingside = true;
         assert_eq!(CastleRights::from_str("Qk").unwrap(), rights);
-        assert!(CastleRights::from_str("abc").is_err());
+        assert_eq!(
+            CastleRights::from_str("abc"),
+            Err(FenError::InvalidCastleRights('a'))
+        );
+        assert_eq!(
+            CastleRights::from_str("KQkqK"),
+            Err(FenError::InvalidCastleRights('K'))
+        );
     }
 
     #[test]
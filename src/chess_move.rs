@@ -1,6 +1,10 @@
 use crate::pieces::Piece;
 use crate::square::Square;
 
+use anyhow::{Error, bail};
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub struct ChessMove {
     pub source: Square,
@@ -18,3 +22,65 @@ impl ChessMove {
         }
     }
 }
+
+impl fmt::Display for ChessMove {
+    /// Formats the move in UCI coordinate notation (e.g., "e2e4" or "e7e8q").
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.source, self.dest)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ChessMove {
+    type Err = Error;
+
+    /// Parses a move in UCI coordinate notation, without checking it against any position.
+    /// Use `Board::parse_uci_move` to get a legal move.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() || !(4..=5).contains(&s.len()) {
+            bail!("invalid move '{}'", s);
+        }
+
+        let source = Square::from_str(&s[0..2])?;
+        let dest = Square::from_str(&s[2..4])?;
+        let promotion = match &s[4..] {
+            "" => None,
+            "n" => Some(Piece::Knight),
+            "b" => Some(Piece::Bishop),
+            "r" => Some(Piece::Rook),
+            "q" => Some(Piece::Queen),
+            _ => bail!("invalid promotion in move '{}'", s),
+        };
+
+        Ok(ChessMove::new(source, dest, promotion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chess_move_uci() {
+        for uci in ["e2e4", "e7e8q", "a2a1n", "e1h1"] {
+            assert_eq!(format!("{}", ChessMove::from_str(uci).unwrap()), uci);
+        }
+        assert_eq!(
+            ChessMove::from_str("g7g8r").unwrap(),
+            ChessMove::new(
+                Square::from_str("g7").unwrap(),
+                Square::from_str("g8").unwrap(),
+                Some(Piece::Rook)
+            )
+        );
+
+        for uci in [
+            "", "e2", "e2e9", "e2e4 ", "e7e8k", "e7e8Q", "e2e4e5", "0000",
+        ] {
+            assert!(ChessMove::from_str(uci).is_err(), "{}", uci);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use crate::board::Board;
+use crate::board_builder::BoardBuilder;
+use crate::chess_move::ChessMove;
+
+use anyhow::{Context, Error, Result, bail};
+use std::fmt;
+use std::str::FromStr;
+
+/// A position in Extended Position Description: the first four fields of a FEN
+/// followed by operations, each an opcode and its operands ended by a semicolon,
+/// like `bm Nf3; id "WAC.001";` or `D1 20; D2 400;`.
+///
+/// Operations are kept in the order they were read. The move counters come from the
+/// `hmvc` and `fmvn` operations, or from two numbers right after the fourth field as
+/// found in perft suites. Inside quoted operands, `\"` and `\\` stand for a quote and
+/// a backslash.
+#[derive(Clone, Debug)]
+pub struct Epd {
+    board: Board,
+    operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    #[inline(always)]
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            operations: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    #[inline(always)]
+    pub fn operations(&self) -> &[(String, Vec<String>)] {
+        &self.operations
+    }
+
+    /// Returns the operands of `opcode`, if the operation is present.
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Sets the operands of `opcode`, replacing the operation if present.
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(op, _)| op == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn remove(&mut self, opcode: &str) -> Option<Vec<String>> {
+        let idx = self.operations.iter().position(|(op, _)| op == opcode)?;
+        Some(self.operations.remove(idx).1)
+    }
+
+    fn get_single(&self, opcode: &str) -> Option<&str> {
+        self.get(opcode)?.first().map(String::as_str)
+    }
+
+    /// Returns the position identifier, from the `id` operation.
+    #[inline(always)]
+    pub fn id(&self) -> Option<&str> {
+        self.get_single("id")
+    }
+
+    /// Returns the comment `c0` to `c9` with the given index.
+    pub fn comment(&self, index: usize) -> Option<&str> {
+        self.get_single(&format!("c{}", index))
+    }
+
+    /// Returns the best moves, from the SAN operands of `bm`.
+    pub fn best_moves(&self) -> Result<Vec<ChessMove>> {
+        self.parse_moves("bm")
+    }
+
+    /// Returns the moves to avoid, from the SAN operands of `am`.
+    pub fn avoid_moves(&self) -> Result<Vec<ChessMove>> {
+        self.parse_moves("am")
+    }
+
+    fn parse_moves(&self, opcode: &str) -> Result<Vec<ChessMove>> {
+        self.get(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san| self.board.parse_san(san))
+            .collect()
+    }
+
+    /// Returns the predicted variation, from the `pv` operation, with each SAN move
+    /// read in the position left by the previous ones.
+    pub fn pv(&self) -> Result<Vec<ChessMove>> {
+        let mut board = self.board.clone();
+        let mut moves = Vec::new();
+        for san in self.get("pv").unwrap_or_default() {
+            let m = board.parse_san(san)?;
+            board = board.make_move(m);
+            moves.push(m);
+        }
+        Ok(moves)
+    }
+
+    /// Returns the centipawn evaluation, from the `ce` operation.
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        self.get_single("ce")?.parse().ok()
+    }
+
+    /// Returns the expected perft node count at `depth`, from the `D1`, `D2`, ... operations.
+    pub fn perft(&self, depth: usize) -> Option<u64> {
+        self.get_single(&format!("D{}", depth))?.parse().ok()
+    }
+
+    /// Returns the expected perft node counts as `(depth, nodes)` pairs, sorted by depth.
+    pub fn perft_counts(&self) -> Vec<(usize, u64)> {
+        let mut counts: Vec<(usize, u64)> = self
+            .operations
+            .iter()
+            .filter_map(|(op, operands)| {
+                let depth = op.strip_prefix('D')?.parse().ok()?;
+                Some((depth, operands.first()?.parse().ok()?))
+            })
+            .collect();
+        counts.sort();
+        counts
+    }
+}
+
+/// Splits the operations of an EPD into opcodes and operands, keeping quoted strings whole.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            ';' => {
+                if tokens.is_empty() {
+                    continue;
+                }
+                let opcode = tokens.remove(0);
+                operations.push((opcode, std::mem::take(&mut tokens)));
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => string.push(c),
+                            None => bail!("unterminated string in EPD operations '{}'", text),
+                        },
+                        Some(c) => string.push(c),
+                        None => bail!("unterminated string in EPD operations '{}'", text),
+                    }
+                }
+                if tokens.is_empty() {
+                    bail!("EPD operation without an opcode in '{}'", text);
+                }
+                tokens.push(string);
+            }
+            c => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '"' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    // the semicolon of the last operation is often left out
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+    Ok(operations)
+}
+
+impl FromStr for Epd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut fields = Vec::new();
+        let mut rest = s;
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+
+        // perft suites often keep the move counters of the full FEN
+        let mut counters = Vec::new();
+        for _ in 0..2 {
+            let trimmed = rest.trim_start();
+            let end = trimmed
+                .find(|c: char| c.is_whitespace() || c == ';')
+                .unwrap_or(trimmed.len());
+            if end == 0 || !trimmed[..end].chars().all(|c| c.is_ascii_digit()) {
+                break;
+            }
+            counters.push(&trimmed[..end]);
+            rest = &trimmed[end..];
+        }
+
+        let operations = parse_operations(rest)?;
+        let get = |opcode: &str| {
+            operations
+                .iter()
+                .find(|(op, _)| op == opcode)
+                .and_then(|(_, operands)| operands.first())
+                .map(String::as_str)
+        };
+        let halfmove_clock = get("hmvc").or(counters.first().copied()).unwrap_or("0");
+        let fullmove_number = get("fmvn").or(counters.get(1).copied()).unwrap_or("1");
+
+        let fen = format!(
+            "{} {} {}",
+            fields.join(" "),
+            halfmove_clock,
+            fullmove_number
+        );
+        let board = BoardBuilder::from_str(&fen)
+            .and_then(|builder| builder.build())
+            .with_context(|| format!("invalid EPD position '{}'", s))?;
+
+        let epd = Epd { board, operations };
+        epd.best_moves()?;
+        epd.avoid_moves()?;
+        epd.pv()?;
+        Ok(epd)
+    }
+}
+
+impl fmt::Display for Epd {
+    /// Formats the position as EPD. The `id` and `c0` to `c9` operands are always
+    /// quoted, other operands only when they hold whitespace, a semicolon or a quote.
+    /// Move counters off their defaults are written as `hmvc` and `fmvn` unless those
+    /// operations are already there.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = self.board.to_string();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            let quoted = opcode == "id"
+                || (opcode.len() == 2
+                    && opcode.starts_with('c')
+                    && opcode.ends_with(|c: char| c.is_ascii_digit()));
+            for operand in operands {
+                if quoted
+                    || operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"')
+                {
+                    let escaped = operand.replace('\\', "\\\\").replace('"', "\\\"");
+                    write!(f, " \"{}\"", escaped)?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+
+        if self.board.halfmove_clock() != 0 && self.get("hmvc").is_none() {
+            write!(f, " hmvc {};", self.board.halfmove_clock())?;
+        }
+        if self.board.fullmove_number() != 1 && self.get("fmvn").is_none() {
+            write!(f, " fmvn {};", self.board.fullmove_number())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epd_operations() {
+        let epd = Epd::from_str(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"mate in three\"; ce +32000; pv Qg6 fxg6 Nxg6+;",
+        )
+        .unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.comment(0), Some("mate in three"));
+        assert_eq!(epd.comment(1), None);
+        assert_eq!(epd.centipawn_eval(), Some(32000));
+        assert_eq!(
+            epd.best_moves().unwrap(),
+            vec![epd.board().parse_uci_move("g3g6").unwrap()]
+        );
+        assert!(epd.avoid_moves().unwrap().is_empty());
+        assert_eq!(
+            epd.pv()
+                .unwrap()
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>(),
+            ["g3g6", "f7g6", "e5g6"].map(String::from)
+        );
+        assert_eq!(epd.board().halfmove_clock(), 0);
+        assert_eq!(epd.board().fullmove_number(), 1);
+
+        assert_eq!(
+            epd.to_string(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"mate in three\"; ce +32000; pv Qg6 fxg6 Nxg6+;"
+        );
+        assert_eq!(
+            Epd::from_str(&epd.to_string()).unwrap().to_string(),
+            epd.to_string()
+        );
+    }
+
+    #[test]
+    fn test_epd_perft_counts() {
+        let epd = Epd::from_str(
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 ;D1 26 ;D2 568 ;D3 13744 ;D4 314346",
+        )
+        .unwrap();
+        assert_eq!(epd.perft(2), Some(568));
+        assert_eq!(epd.perft(5), None);
+        assert_eq!(
+            epd.perft_counts(),
+            vec![(1, 26), (2, 568), (3, 13744), (4, 314346)]
+        );
+
+        let mut epd = Epd::from_str("4k3/8/8/8/8/8/8/4K3 b - - hmvc 12; fmvn 40;").unwrap();
+        assert_eq!(epd.board().halfmove_clock(), 12);
+        assert_eq!(epd.board().fullmove_number(), 40);
+        epd.set("D1", vec!["5".to_string()]);
+        assert_eq!(epd.remove("hmvc"), Some(vec!["12".to_string()]));
+        // the clock of the board is still written
+        assert_eq!(
+            epd.to_string(),
+            "4k3/8/8/8/8/8/8/4K3 b - - fmvn 40; D1 5; hmvc 12;"
+        );
+    }
+
+    #[test]
+    fn test_epd_round_trip() {
+        let mut epd =
+            Epd::from_str("4k3/8/8/8/8/8/8/4K3 b - - 7 31; id \"say \\\"hi\\\" \\\\ bye\";")
+                .unwrap();
+        assert_eq!(epd.id(), Some("say \"hi\" \\ bye"));
+        epd.set("pm", vec!["a\"b".to_string()]);
+        assert_eq!(
+            epd.to_string(),
+            "4k3/8/8/8/8/8/8/4K3 b - - id \"say \\\"hi\\\" \\\\ bye\"; pm \"a\\\"b\"; hmvc 7; fmvn 31;"
+        );
+
+        let parsed = Epd::from_str(&epd.to_string()).unwrap();
+        assert_eq!(parsed.operations()[..2], epd.operations()[..]);
+        assert_eq!(parsed.board().halfmove_clock(), 7);
+        assert_eq!(parsed.board().fullmove_number(), 31);
+        assert_eq!(parsed.to_string(), epd.to_string());
+    }
+
+    #[test]
+    fn test_epd_errors() {
+        for epd in [
+            "4k3/8/8/8/8/8/8/4K3 w - - bm Ke2 Kd9;",
+            "4k3/8/8/8/8/8/8/4K3 w - - bm Ke3;",
+            "4k3/8/8/8/8/8/8/4K3 w - - id \"unterminated;",
+            "4k3/8/8/8/8/8/8/4K3 w - -  \"x\";",
+            "4k3/8/8/8/8/8/8/8 w - - id \"x\";",
+        ] {
+            assert!(Epd::from_str(epd).is_err(), "{}", epd);
+        }
+    }
+}
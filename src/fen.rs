@@ -0,0 +1,107 @@
+use crate::color::Color;
+use crate::file::ALL_FILES;
+use crate::pieces::Piece;
+use crate::rank::{ALL_RANKS, Rank};
+use crate::square::Square;
+
+use std::error::Error;
+use std::fmt;
+
+/// Error returned when a FEN string, or one of its fields, can't be turned into a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// The FEN has the wrong number of whitespace separated fields.
+    InvalidFieldCount(usize),
+    /// The piece placement doesn't have exactly 8 ranks.
+    InvalidRankCount(usize),
+    /// A rank of the piece placement holds an unknown character.
+    InvalidPiece {
+        rank: Rank,
+        character: char,
+    },
+    /// A rank of the piece placement doesn't describe exactly 8 squares.
+    InvalidRankLength(Rank),
+    InvalidSideToMove(String),
+    /// The castling field holds an unknown or repeated character.
+    InvalidCastleRights(char),
+    InvalidSquare(String),
+    /// The en passant square doesn't follow a double pawn push of the side not to move.
+    InvalidEnPassant(Square),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPieces(Color),
+    PawnOnBackRank(Square),
+    /// A castling right is set, but the king or the rook are not on their home squares.
+    CastleRightsMismatch(char),
+    /// The side that just moved left its king in check.
+    OpponentInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::InvalidFieldCount(n) => write!(f, "expected 4 to 6 fields, found {}", n),
+            FenError::InvalidRankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::InvalidPiece { rank, character } => write!(
+                f,
+                "invalid piece '{}' on rank {}",
+                character,
+                rank.to_index() + 1
+            ),
+            FenError::InvalidRankLength(rank) => {
+                write!(f, "rank {} does not have 8 squares", rank.to_index() + 1)
+            }
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastleRights(c) => write!(f, "invalid castling right '{}'", c),
+            FenError::InvalidSquare(s) => write!(f, "invalid square '{}'", s),
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "invalid en passant square {}", square)
+            }
+            FenError::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+            FenError::MissingKing(color) => write!(f, "{:?} has no king", color),
+            FenError::TooManyKings(color) => write!(f, "{:?} has more than one king", color),
+            FenError::TooManyPieces(color) => write!(f, "{:?} has too many pieces", color),
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on back rank square {}", square),
+            FenError::CastleRightsMismatch(c) => write!(
+                f,
+                "castling right '{}' without king and rook on their home squares",
+                c
+            ),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl Error for FenError {}
+
+/// Writes the piece placement field of a FEN, from the eighth rank down, with
+/// `piece_at` giving the content of each square.
+pub(crate) fn write_placement(
+    f: &mut dyn fmt::Write,
+    piece_at: impl Fn(Square) -> Option<(Piece, Color)>,
+) -> fmt::Result {
+    for rank in ALL_RANKS.iter().rev() {
+        let mut empty = 0;
+        for file in ALL_FILES.iter() {
+            if let Some((piece, color)) = piece_at(Square::new(*rank, *file)) {
+                if empty != 0 {
+                    write!(f, "{}", empty)?;
+                    empty = 0;
+                }
+                write!(f, "{}", piece.to_string(color))?;
+            } else {
+                empty += 1;
+            }
+        }
+        if empty != 0 {
+            write!(f, "{}", empty)?;
+        }
+        if *rank != Rank::First {
+            write!(f, "/")?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,402 @@
+use crate::board::{Board, BoardStatus};
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+
+use anyhow::{Result, bail};
+
+/// The final result of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    #[inline(always)]
+    pub fn win_for(color: Color) -> Self {
+        match color {
+            Color::White => GameResult::WhiteWins,
+            Color::Black => GameResult::BlackWins,
+        }
+    }
+}
+
+/// The reason a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    /// Drawn automatically after the same position occurred five times.
+    FivefoldRepetition,
+    /// Drawn automatically after 75 moves by each side without a capture or pawn move.
+    SeventyFiveMoveRule,
+    /// Draw claimed after the same position occurred three times.
+    ThreefoldRepetition,
+    /// Draw claimed after 50 moves by each side without a capture or pawn move.
+    FiftyMoveRule,
+    DrawAgreement,
+    Resignation,
+}
+
+/// A game of chess: a starting position plus the moves played from it.
+///
+/// Besides the automatic game endings (checkmate, stalemate, insufficient material,
+/// fivefold repetition and the 75-move rule), it tracks the events a game server needs:
+/// draw claims, draw offers and resignations.
+#[derive(Clone)]
+pub struct Game {
+    positions: Vec<Board>,
+    moves: Vec<ChessMove>,
+    draw_offer: Option<Color>,
+    ended: Option<(GameResult, Termination)>,
+}
+
+impl Game {
+    /// Creates a game from the standard starting position.
+    #[inline(always)]
+    pub fn new() -> Self {
+        Self::new_with_board(Board::default())
+    }
+
+    /// Creates a game starting from an arbitrary position.
+    #[inline(always)]
+    pub fn new_with_board(board: Board) -> Self {
+        Self {
+            positions: vec![board],
+            moves: Vec::new(),
+            draw_offer: None,
+            ended: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn start_position(&self) -> &Board {
+        &self.positions[0]
+    }
+
+    #[inline(always)]
+    pub fn current_position(&self) -> &Board {
+        self.positions.last().unwrap()
+    }
+
+    /// Returns every position of the game, from the start to the current one.
+    #[inline(always)]
+    pub fn positions(&self) -> &[Board] {
+        &self.positions
+    }
+
+    #[inline(always)]
+    pub fn moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    #[inline(always)]
+    pub fn side_to_move(&self) -> Color {
+        self.current_position().side_to_move()
+    }
+
+    /// Returns the color with a pending draw offer, if any.
+    #[inline(always)]
+    pub fn draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    /// Plays `m` on the current position. Fails if the game is over or the move is illegal.
+    ///
+    /// Moving declines a pending draw offer from the opponent.
+    pub fn make_move(&mut self, m: ChessMove) -> Result<()> {
+        if self.result().is_some() {
+            bail!("the game is already over");
+        }
+
+        let board = self.current_position();
+        if !board.is_legal(m) {
+            bail!("illegal move {:?} in position {}", m, board);
+        }
+
+        let next = board.make_move(m);
+        if self.draw_offer == Some(!board.side_to_move()) {
+            self.draw_offer = None;
+        }
+
+        self.positions.push(next);
+        self.moves.push(m);
+        Ok(())
+    }
+
+    /// Takes back the last move. Returns the move that was undone.
+    ///
+    /// Endings that depend on the position, such as a draw claim, no longer apply
+    /// afterwards, but a resignation or an agreed draw still stands.
+    pub fn undo(&mut self) -> Option<ChessMove> {
+        let m = self.moves.pop()?;
+        self.positions.pop();
+        self.draw_offer = None;
+        if let Some((_, Termination::ThreefoldRepetition | Termination::FiftyMoveRule)) = self.ended
+        {
+            self.ended = None;
+        }
+        Some(m)
+    }
+
+    /// Returns how many times the current position has occurred in the game,
+    /// including the current occurrence.
+    pub fn repetition_count(&self) -> usize {
+        let current = self.current_position();
+        // positions before the last capture or pawn move can never repeat
+        let plies = (current.halfmove_clock() as usize).min(self.positions.len() - 1);
+
+        self.positions
+            .iter()
+            .rev()
+            .take(plies + 1)
+            .step_by(2)
+            .filter(|board| board.get_hash() == current.get_hash())
+            .count()
+    }
+
+    /// Returns true if the side to move may claim a draw by threefold repetition
+    /// or by the fifty-move rule.
+    #[inline(always)]
+    pub fn can_declare_draw(&self) -> bool {
+        self.draw_claim().is_some()
+    }
+
+    fn draw_claim(&self) -> Option<Termination> {
+        if self.result().is_some() {
+            None
+        } else if self.repetition_count() >= 3 {
+            Some(Termination::ThreefoldRepetition)
+        } else if self.current_position().halfmove_clock() >= 100 {
+            Some(Termination::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// Claims a draw by threefold repetition or by the fifty-move rule.
+    pub fn declare_draw(&mut self) -> Result<()> {
+        match self.draw_claim() {
+            Some(termination) => {
+                self.ended = Some((GameResult::Draw, termination));
+                Ok(())
+            }
+            None => bail!("no draw can be claimed in this position"),
+        }
+    }
+
+    /// Offers a draw on behalf of `color`, to be accepted by the opponent.
+    pub fn offer_draw(&mut self, color: Color) -> Result<()> {
+        if self.result().is_some() {
+            bail!("the game is already over");
+        }
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    /// Accepts the draw offered by the opponent of `color`.
+    pub fn accept_draw(&mut self, color: Color) -> Result<()> {
+        if self.result().is_some() {
+            bail!("the game is already over");
+        }
+        if self.draw_offer != Some(!color) {
+            bail!("there is no draw offer to accept");
+        }
+        self.draw_offer = None;
+        self.ended = Some((GameResult::Draw, Termination::DrawAgreement));
+        Ok(())
+    }
+
+    pub fn resign(&mut self, color: Color) -> Result<()> {
+        if self.result().is_some() {
+            bail!("the game is already over");
+        }
+        self.draw_offer = None;
+        self.ended = Some((GameResult::win_for(!color), Termination::Resignation));
+        Ok(())
+    }
+
+    /// Returns the result of the game, or `None` while it is still ongoing.
+    #[inline(always)]
+    pub fn result(&self) -> Option<GameResult> {
+        self.outcome().map(|(result, _)| result)
+    }
+
+    /// Returns why the game ended, or `None` while it is still ongoing.
+    #[inline(always)]
+    pub fn termination(&self) -> Option<Termination> {
+        self.outcome().map(|(_, termination)| termination)
+    }
+
+    fn outcome(&self) -> Option<(GameResult, Termination)> {
+        if self.ended.is_some() {
+            return self.ended;
+        }
+
+        let board = self.current_position();
+        match board.status() {
+            BoardStatus::Checkmate => {
+                return Some((
+                    GameResult::win_for(!board.side_to_move()),
+                    Termination::Checkmate,
+                ));
+            }
+            BoardStatus::Stalemate => return Some((GameResult::Draw, Termination::Stalemate)),
+            BoardStatus::Ongoing => {}
+        }
+
+        if board.is_insufficient_material() {
+            Some((GameResult::Draw, Termination::InsufficientMaterial))
+        } else if self.repetition_count() >= 5 {
+            Some((GameResult::Draw, Termination::FivefoldRepetition))
+        } else if board.halfmove_clock() >= 150 {
+            Some((GameResult::Draw, Termination::SeventyFiveMoveRule))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn mv(m: &str) -> ChessMove {
+        ChessMove::new(m[0..2].parse().unwrap(), m[2..4].parse().unwrap(), None)
+    }
+
+    fn play(game: &mut Game, moves: &[&str]) {
+        for m in moves {
+            game.make_move(mv(m)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_game_checkmate() {
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4"]);
+        assert_eq!(game.result(), None);
+        play(&mut game, &["d8h4"]);
+        assert_eq!(game.result(), Some(GameResult::BlackWins));
+        assert_eq!(game.termination(), Some(Termination::Checkmate));
+        assert!(game.make_move(mv("a2a3")).is_err());
+
+        assert_eq!(game.undo(), Some(mv("d8h4")));
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn test_game_illegal_move() {
+        let mut game = Game::new();
+        assert!(game.make_move(mv("e2e5")).is_err());
+        assert!(game.make_move(mv("e7e5")).is_err());
+        assert!(game.moves().is_empty());
+    }
+
+    #[test]
+    fn test_game_repetition() {
+        let mut game = Game::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        play(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 2);
+        assert!(!game.can_declare_draw());
+
+        play(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        assert!(game.can_declare_draw());
+
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle[..2]);
+        assert_eq!(game.result(), None);
+        play(&mut game, &shuffle[2..]);
+        assert_eq!(game.repetition_count(), 5);
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.termination(), Some(Termination::FivefoldRepetition));
+
+        game.undo();
+        assert!(game.can_declare_draw());
+        game.declare_draw().unwrap();
+        assert_eq!(game.termination(), Some(Termination::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn test_game_move_rules() {
+        let board = Board::from_str("8/8/4k3/8/8/3K4/5R2/8 w - - 99 80").unwrap();
+        let mut game = Game::new_with_board(board);
+        assert!(!game.can_declare_draw());
+        assert!(game.declare_draw().is_err());
+        play(&mut game, &["f2f1"]);
+        assert!(game.can_declare_draw());
+        game.declare_draw().unwrap();
+        assert_eq!(game.termination(), Some(Termination::FiftyMoveRule));
+
+        let board = Board::from_str("8/8/4k3/8/8/3K4/5R2/8 w - - 149 80").unwrap();
+        let mut game = Game::new_with_board(board);
+        play(&mut game, &["f2f1"]);
+        assert_eq!(game.termination(), Some(Termination::SeventyFiveMoveRule));
+    }
+
+    #[test]
+    fn test_game_resign_and_draw_offer() {
+        let mut game = Game::new();
+        game.resign(Color::White).unwrap();
+        assert_eq!(game.result(), Some(GameResult::BlackWins));
+        assert_eq!(game.termination(), Some(Termination::Resignation));
+        assert!(game.resign(Color::Black).is_err());
+
+        let mut game = Game::new();
+        game.offer_draw(Color::White).unwrap();
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.draw_offer(), Some(Color::White));
+        // the offer can't be accepted by the side that made it
+        assert!(game.accept_draw(Color::White).is_err());
+
+        // moving declines the offer
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.draw_offer(), None);
+        assert!(game.accept_draw(Color::Black).is_err());
+
+        game.offer_draw(Color::White).unwrap();
+        game.accept_draw(Color::Black).unwrap();
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.termination(), Some(Termination::DrawAgreement));
+    }
+
+    #[test]
+    fn test_game_undo_termination() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5"]);
+        game.resign(Color::Black).unwrap();
+        assert_eq!(game.undo(), Some(mv("e7e5")));
+        assert_eq!(game.termination(), Some(Termination::Resignation));
+        assert!(game.make_move(mv("e7e5")).is_err());
+
+        let mut game = Game::new();
+        play(&mut game, &["e2e4"]);
+        game.offer_draw(Color::White).unwrap();
+        game.accept_draw(Color::Black).unwrap();
+        game.undo();
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.termination(), Some(Termination::DrawAgreement));
+
+        // a draw claim only holds in the position it was claimed in
+        let mut game = Game::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle);
+        game.declare_draw().unwrap();
+        assert_eq!(game.termination(), Some(Termination::ThreefoldRepetition));
+        game.undo();
+        assert_eq!(game.result(), None);
+    }
+}
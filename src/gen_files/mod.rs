@@ -7,6 +7,7 @@ pub mod lines;
 pub mod magics;
 pub mod pawn;
 pub mod rays;
+pub mod zobrist;
 
 pub use between::write_between;
 pub use chessboard::write_chessboard_utils;
@@ -16,3 +17,4 @@ pub use lines::write_lines;
 pub use magics::{gen_all_magic, write_magics};
 pub use pawn::{write_pawn_attacks, write_pawn_moves};
 pub use rays::write_rays;
+pub use zobrist::write_zobrist;
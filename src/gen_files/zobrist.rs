@@ -0,0 +1,42 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+use std::fs::File;
+use std::io::Write;
+
+// fixed seed, so the keys (and therefore the hashes) are stable across builds
+const ZOBRIST_SEED: u64 = 0x4C55_4E41_4348_4553;
+
+pub fn write_zobrist(f: &mut File) -> std::io::Result<()> {
+    let mut rng = SmallRng::seed_from_u64(ZOBRIST_SEED);
+
+    writeln!(f, "const ZOBRIST_PIECES: [[[u64; 64]; 6]; 2] = [")?;
+    for _color in 0..2 {
+        writeln!(f, "  [")?;
+        for _piece in 0..6 {
+            writeln!(f, "    [")?;
+            for _square in 0..64 {
+                writeln!(f, "      {},", rng.random::<u64>())?;
+            }
+            writeln!(f, "    ],")?;
+        }
+        writeln!(f, "  ],")?;
+    }
+    writeln!(f, "];")?;
+
+    writeln!(f, "const ZOBRIST_CASTLES: [[u64; 2]; 2] = [")?;
+    for _color in 0..2 {
+        writeln!(f, "    [{}, {}],", rng.random::<u64>(), rng.random::<u64>())?;
+    }
+    writeln!(f, "];")?;
+
+    writeln!(f, "const ZOBRIST_EN_PASSANT: [u64; 8] = [")?;
+    for _file in 0..8 {
+        writeln!(f, "    {},", rng.random::<u64>())?;
+    }
+    writeln!(f, "];")?;
+
+    writeln!(f, "const ZOBRIST_SIDE: u64 = {};", rng.random::<u64>())?;
+
+    Ok(())
+}
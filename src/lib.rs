@@ -1,13 +1,24 @@
 pub mod bitboard;
 pub mod board;
+pub mod board_builder;
 pub mod castle_rights;
 pub mod chess_move;
 pub mod color;
+pub mod epd;
+pub mod fen;
 pub mod file;
+pub mod game;
 pub mod gen_files;
 pub mod magic;
 pub mod movegen;
+pub mod perft;
+pub mod pgn;
 pub mod piece_moves;
 pub mod pieces;
 pub mod rank;
+pub mod san;
+pub mod search;
 pub mod square;
+pub mod uci;
+pub mod xboard;
+pub mod zobrist;
@@ -1,15 +1,535 @@
-use luna_chess::board::Board;
+use luna_chess::board::{Board, BoardStatus};
+use luna_chess::chess_move::ChessMove;
 use luna_chess::movegen::MoveGen;
+use luna_chess::perft::{
+    DivergenceKind, DivideFile, PerftTable, ReferenceSource, UciEngine, find_divergence,
+    perft_hashed, perft_parallel,
+};
+
+use anyhow::{Context, Result, bail};
 use std::env;
+use std::fmt;
+use std::fs;
+use std::process;
+use std::str::FromStr;
 use std::time::Instant;
 
+const USAGE: &str = "usage: luna-chess <command> [--json] [args]
+
+commands:
+  perft <depth> [fen] [--threads <n>] [--hash <mb>]
+                            count the leaf nodes at depth
+  divide <depth> [fen]      count the nodes below each legal move
+  diff <depth> <file> [fen] walk down to the first move whose count differs from
+                            a reference divide; --engine <command> instead of
+                            <file> asks a UCI engine supporting `go perft`
+  show [fen]                draw the board
+  moves [fen] [--san]       list the legal moves
+  play [--fen <fen>] <move>...
+                            play UCI or SAN moves and print the resulting FEN
+  validate <fen>            check a FEN
+
+The FEN is the start position if left out. With --lenient, positions that can't
+arise in a game are accepted as long as moves can be generated for them, and
+inconsistent castling rights and en passant squares are dropped. With --json the
+output, errors included, is a single JSON value. Without a command, times a perft
+of depth 6 from the start position.";
+
+/// A JSON value, just enough to write the output of the commands.
+enum Json {
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string(value: impl ToString) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => {
+                write!(f, "\"")?;
+                for c in value.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", Json::string(key), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// The arguments of a command, with the options taken out as they are read.
+struct Args {
+    args: Vec<String>,
+    json: bool,
+    lenient: bool,
+}
+
+impl Args {
+    /// Removes the flag `name`, returning whether it was present.
+    fn flag(&mut self, name: &str) -> bool {
+        let idx = self.args.iter().position(|arg| arg == name);
+        idx.map(|idx| self.args.remove(idx)).is_some()
+    }
+
+    /// Removes the option `name` and its value.
+    fn option(&mut self, name: &str) -> Result<Option<String>> {
+        let Some(idx) = self.args.iter().position(|arg| arg == name) else {
+            return Ok(None);
+        };
+        if idx + 1 >= self.args.len() {
+            bail!("{} expects a value", name);
+        }
+        self.args.remove(idx);
+        Ok(Some(self.args.remove(idx)))
+    }
+
+    /// Removes the option `name` and its value, which must be a positive number.
+    fn number_option(&mut self, name: &str) -> Result<Option<usize>> {
+        match self.option(name)? {
+            Some(value) => match value.parse() {
+                Ok(value) if value > 0 => Ok(Some(value)),
+                _ => bail!("{} expects a positive number", name),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Removes the first positional argument, which must be a depth of at least 1.
+    fn depth(&mut self) -> Result<usize> {
+        if self.args.is_empty() {
+            bail!("missing depth");
+        }
+        let depth = self.args.remove(0);
+        match depth.parse() {
+            Ok(0) => bail!("depth must be at least 1"),
+            Ok(depth) => Ok(depth),
+            Err(err) => Err(err).with_context(|| format!("invalid depth '{}'", depth)),
+        }
+    }
+
+    /// Reads the remaining arguments as a FEN, the start position if there are none,
+    /// only checking what the move generator needs with `--lenient`.
+    fn board(&mut self) -> Result<Board> {
+        if self.args.is_empty() {
+            return Ok(Board::default());
+        }
+        let fen = std::mem::take(&mut self.args).join(" ");
+        let board = if self.lenient {
+            Board::from_str_lenient(&fen)
+        } else {
+            Board::from_str(&fen)
+        };
+        board.with_context(|| format!("invalid FEN '{}'", fen))
+    }
+}
+
 fn main() {
-    let depth = env::args().nth(1).and_then(|s| s.parse().ok()).unwrap_or(6);
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let lenient = args.iter().any(|arg| arg == "--lenient");
+    args.retain(|arg| arg != "--json" && arg != "--lenient");
 
-    let board = Board::default();
+    let command = if args.is_empty() {
+        // `luna-chess` alone times a perft 6, as it always did
+        args.push("6".to_string());
+        "perft".to_string()
+    } else if args[0].parse::<usize>().is_ok() {
+        // `luna-chess <depth>` times a perft, as it always did
+        "perft".to_string()
+    } else {
+        args.remove(0)
+    };
+    let mut args = Args {
+        args,
+        json,
+        lenient,
+    };
+
+    let result = match command.as_str() {
+        "perft" => perft(&mut args),
+        "divide" => divide(&mut args),
+        "diff" => diff(&mut args),
+        "show" => show(&mut args),
+        "moves" => moves(&mut args),
+        "play" => play(&mut args),
+        "validate" => validate(&mut args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!(
+            "unknown command '{}'\n\n{}",
+            command,
+            USAGE
+        )),
+    };
+
+    if let Err(err) = result {
+        if json {
+            println!(
+                "{}",
+                Json::Object(vec![("error", Json::string(format!("{:#}", err)))])
+            );
+        } else {
+            eprintln!("{:#}", err);
+        }
+        process::exit(1);
+    }
+}
+
+/// Returns the moves as objects holding both their UCI and SAN.
+fn moves_json(board: &Board, moves: impl IntoIterator<Item = ChessMove>) -> Json {
+    Json::Array(
+        moves
+            .into_iter()
+            .map(|m| {
+                Json::Object(vec![
+                    ("uci", Json::string(m)),
+                    ("san", Json::string(board.san(m))),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn status_str(board: &Board) -> &'static str {
+    match board.status() {
+        BoardStatus::Ongoing => "ongoing",
+        BoardStatus::Checkmate => "checkmate",
+        BoardStatus::Stalemate => "stalemate",
+    }
+}
+
+/// `perft <depth> [fen]`: times the leaf node count, spread over `--threads` threads or
+/// cached in a `--hash` table of the given size in MB.
+fn perft(args: &mut Args) -> Result<()> {
+    let threads = args.number_option("--threads")?;
+    let hash = args.number_option("--hash")?;
+    if threads.is_some() && hash.is_some() {
+        bail!("--threads and --hash can't be used together");
+    }
+    let threads = threads.unwrap_or(1);
+    let depth = args.depth()?;
+    let board = args.board()?;
 
     let start = Instant::now();
-    let _ = MoveGen::perft_test(&board, depth);
+    let (nodes, mode) = match hash {
+        Some(megabytes) => (
+            perft_hashed(&board, depth, &mut PerftTable::new(megabytes)),
+            format!("{megabytes} MB hash"),
+        ),
+        None => (
+            perft_parallel(&board, depth, threads),
+            format!("{threads} threads"),
+        ),
+    };
     let duration = start.elapsed();
-    println!("Perft {depth} in: {:?}", duration);
+    let nps = (nodes as f64 / duration.as_secs_f64().max(1e-9)) as u64;
+
+    if args.json {
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("fen", Json::string(&board)),
+                ("depth", Json::Number(depth as u64)),
+                ("nodes", Json::Number(nodes as u64)),
+                ("time_ms", Json::Number(duration.as_millis() as u64)),
+                ("nps", Json::Number(nps)),
+            ])
+        );
+    } else {
+        println!("Perft {depth} in: {:?} ({nodes} nodes, {mode})", duration);
+    }
+    Ok(())
+}
+
+/// `divide <depth> [fen]`: prints the nodes below each root move, in the same format
+/// as Stockfish's `go perft`, so both outputs can be diffed.
+fn divide(args: &mut Args) -> Result<()> {
+    let depth = args.depth()?;
+    let board = args.board()?;
+    let divide = MoveGen::perft_divide(&board, depth);
+    let total: usize = divide.iter().map(|(_, nodes)| nodes).sum();
+
+    if args.json {
+        let moves = divide
+            .iter()
+            .map(|(m, nodes)| {
+                Json::Object(vec![
+                    ("move", Json::string(m)),
+                    ("nodes", Json::Number(*nodes as u64)),
+                ])
+            })
+            .collect();
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("fen", Json::string(&board)),
+                ("depth", Json::Number(depth as u64)),
+                ("moves", Json::Array(moves)),
+                ("nodes", Json::Number(total as u64)),
+            ])
+        );
+    } else {
+        for (m, nodes) in divide {
+            println!("{}: {}", m, nodes);
+        }
+        println!();
+        println!("Nodes searched: {}", total);
+    }
+    Ok(())
+}
+
+/// `diff <depth> <file> [fen]` or `diff <depth> --engine <command> [fen]`: compares our
+/// divide with a reference and walks down into the first move whose count differs,
+/// until the missing or extra move is found.
+///
+/// The file holds the reference divide of the root position, optionally followed by
+/// blocks for deeper positions, each starting with a `fen <FEN>` or `position` line.
+/// With `--engine`, the counts are asked from a UCI engine supporting `go perft`.
+fn diff(args: &mut Args) -> Result<()> {
+    let engine = args.option("--engine")?;
+    let depth = args.depth()?;
+    let file = match engine {
+        Some(_) => None,
+        None if args.args.is_empty() => bail!("missing reference file"),
+        None => Some(args.args.remove(0)),
+    };
+    let board = args.board()?;
+
+    let mut source: Box<dyn ReferenceSource> = match (engine, file) {
+        (Some(command), _) => Box::new(UciEngine::new(&command)?),
+        (None, Some(file)) => {
+            let text =
+                fs::read_to_string(&file).with_context(|| format!("failed to read '{}'", file))?;
+            Box::new(DivideFile::new(&board, &text)?)
+        }
+        (None, None) => unreachable!(),
+    };
+    let divergence = find_divergence(&board, depth, source.as_mut())?;
+
+    if args.json {
+        let (result, m) = match &divergence.kind {
+            DivergenceKind::Match => ("match", None),
+            DivergenceKind::MissingMove(m) => ("missing", Some(m.clone())),
+            DivergenceKind::ExtraMove(m) => ("extra", Some(m.to_string())),
+            DivergenceKind::NoReference { .. } => ("no_reference", None),
+            DivergenceKind::CountMismatch { chess_move, .. } => {
+                ("count_mismatch", Some(chess_move.to_string()))
+            }
+        };
+        let mut fields = vec![
+            ("result", Json::string(result)),
+            ("fen", Json::string(&divergence.board)),
+            (
+                "path",
+                Json::Array(divergence.path.iter().map(Json::string).collect()),
+            ),
+        ];
+        if let Some(m) = m {
+            fields.push(("move", Json::String(m)));
+        }
+        match divergence.kind {
+            DivergenceKind::NoReference { depth } => {
+                fields.push(("depth", Json::Number(depth as u64)));
+            }
+            DivergenceKind::CountMismatch {
+                expected, found, ..
+            } => {
+                fields.push(("expected", Json::Number(expected as u64)));
+                fields.push(("found", Json::Number(found as u64)));
+            }
+            _ => {}
+        }
+        println!("{}", Json::Object(fields));
+    } else {
+        println!("{}", divergence);
+    }
+    Ok(())
+}
+
+/// `show [fen]`: draws the board, followed by the state the drawing leaves out.
+fn show(args: &mut Args) -> Result<()> {
+    let board = args.board()?;
+    let fen = board.to_string();
+    let fields: Vec<&str> = fen.split(' ').collect();
+
+    if args.json {
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("fen", Json::string(&fen)),
+                ("side_to_move", Json::string(fields[1])),
+                ("castling", Json::string(fields[2])),
+                ("en_passant", Json::string(fields[3])),
+                (
+                    "halfmove_clock",
+                    Json::Number(board.halfmove_clock() as u64)
+                ),
+                (
+                    "fullmove_number",
+                    Json::Number(board.fullmove_number() as u64)
+                ),
+                ("chess960", Json::Bool(board.is_chess960())),
+                (
+                    "in_check",
+                    Json::Bool(!board.get_checkers_bitboard().is_empty())
+                ),
+                ("status", Json::string(status_str(&board))),
+                ("hash", Json::string(format!("{:016x}", board.get_hash()))),
+            ])
+        );
+    } else {
+        println!("{:?}", board);
+        println!();
+        println!("FEN:    {}", fen);
+        println!("Status: {}", status_str(&board));
+        println!("Hash:   {:016x}", board.get_hash());
+    }
+    Ok(())
+}
+
+/// `moves [fen] [--san]`: lists the legal moves, in UCI or with `--san` in SAN.
+fn moves(args: &mut Args) -> Result<()> {
+    let san = args.flag("--san");
+    let board = args.board()?;
+    let moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+
+    if args.json {
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("fen", Json::string(&board)),
+                ("moves", moves_json(&board, moves)),
+            ])
+        );
+    } else {
+        for m in moves {
+            if san {
+                println!("{}", board.san(m));
+            } else {
+                println!("{}", m);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `play [--fen <fen>] <move>...`: plays the moves, each in UCI or SAN, and prints the FEN
+/// of the resulting position.
+fn play(args: &mut Args) -> Result<()> {
+    let mut board = match args.option("--fen")? {
+        Some(fen) => Board::from_str(&fen).with_context(|| format!("invalid FEN '{}'", fen))?,
+        None => Board::default(),
+    };
+
+    let start = board.clone();
+    let mut played = Vec::new();
+    for (idx, text) in args.args.iter().enumerate() {
+        let m = board
+            .parse_uci_move(text)
+            .or_else(|_| board.parse_san(text))
+            .with_context(|| format!("move {} '{}' in {}", idx + 1, text, board))?;
+        played.push(m);
+        board = board.make_move(m);
+    }
+
+    if args.json {
+        // the SAN of each move is relative to the position it was played in
+        let mut position = start;
+        let moves = played
+            .iter()
+            .map(|&m| {
+                let json = Json::Object(vec![
+                    ("uci", Json::string(m)),
+                    ("san", Json::string(position.san(m))),
+                ]);
+                position = position.make_move(m);
+                json
+            })
+            .collect();
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("fen", Json::string(&board)),
+                ("moves", Json::Array(moves)),
+                ("status", Json::string(status_str(&board))),
+            ])
+        );
+    } else {
+        println!("{}", board);
+    }
+    Ok(())
+}
+
+/// `validate <fen>`: checks that the FEN describes a legal position, exiting with an
+/// error otherwise.
+fn validate(args: &mut Args) -> Result<()> {
+    if args.args.is_empty() {
+        bail!("missing FEN");
+    }
+    let fen = args.args.join(" ");
+    let board = match Board::from_str(&fen) {
+        Ok(board) => board,
+        Err(err) if args.json => {
+            println!(
+                "{}",
+                Json::Object(vec![
+                    ("valid", Json::Bool(false)),
+                    ("fen", Json::string(&fen)),
+                    ("error", Json::string(err)),
+                ])
+            );
+            process::exit(1);
+        }
+        Err(err) => return Err(err).with_context(|| format!("invalid FEN '{}'", fen)),
+    };
+
+    if args.json {
+        println!(
+            "{}",
+            Json::Object(vec![
+                ("valid", Json::Bool(true)),
+                ("fen", Json::string(board))
+            ])
+        );
+    } else {
+        println!("valid: {}", board);
+    }
+    Ok(())
 }
@@ -84,6 +84,44 @@ impl MoveGen {
             result
         }
     }
+
+    /// Counts the leaf nodes below each legal move at `depth`, for comparing against
+    /// the per-move breakdown of another engine, like Stockfish's `go perft`.
+    ///
+    /// The breakdown is empty at depth 0, where the only node is the root itself.
+    pub fn perft_divide(board: &Board, depth: usize) -> Vec<(ChessMove, usize)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        MoveGen::new_legal(board)
+            .map(|m| {
+                let nodes = if depth == 1 {
+                    1
+                } else {
+                    MoveGen::perft_test(&board.make_move(m), depth - 1)
+                };
+                (m, nodes)
+            })
+            .collect()
+    }
+
+    /// Same as [`MoveGen::perft_test`], but walks the tree with make/unmake
+    /// on a single board instead of copying it at every ply.
+    pub fn perft_test_in_place(board: &mut Board, depth: usize) -> usize {
+        let movements = MoveGen::new_legal(board);
+
+        let mut result = 0;
+        if depth == 1 {
+            movements.len()
+        } else {
+            for m in movements {
+                let undo = board.make_move_in_place(m);
+                result += MoveGen::perft_test_in_place(board, depth - 1);
+                board.unmake_move(m, undo);
+            }
+            result
+        }
+    }
 }
 
 impl Iterator for MoveGen {
@@ -141,153 +179,62 @@ impl ExactSizeIterator for MoveGen {
 mod tests {
     use super::*;
 
-    fn movegen_perft_test(fen: &str, depth: usize, result: usize) {
-        let board: Board = fen.parse().unwrap();
-        assert_eq!(MoveGen::perft_test(&board, depth), result);
-    }
-
-    #[test]
-    fn movegen_max_movents() {
-        let fen = "R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1";
-        let board: Board = fen.parse().unwrap();
-        let movements = MoveGen::new_legal(&board);
-        assert_eq!(movements.len(), 218);
-    }
-
-    #[test]
-    fn movegen_perft() {
-        movegen_perft_test("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661072);
-    }
-
-    #[test]
-    fn movegen_perft_1() {
-        movegen_perft_test("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1", 6, 824064);
-        // Invalid FEN
-    }
-
-    #[test]
-    fn movegen_perft_2() {
-        movegen_perft_test("8/8/1k6/8/2pP4/8/5BK1/8 b - d3 0 1", 6, 824064);
-        // Invalid FEN
-    }
-
-    #[test]
-    fn movegen_perft_3() {
-        movegen_perft_test("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1440467);
-    }
-
-    #[test]
-    fn movegen_perft_4() {
-        movegen_perft_test("8/5k2/8/2Pp4/2B5/1K6/8/8 w - d6 0 1", 6, 1440467);
-    }
-
     #[test]
-    fn movegen_perft_5() {
-        movegen_perft_test("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661072);
-    }
-
-    #[test]
-    fn movegen_perft_6() {
-        movegen_perft_test("4k2r/8/8/8/8/8/8/5K2 b k - 0 1", 6, 661072);
-    }
-
-    #[test]
-    fn movegen_perft_7() {
-        movegen_perft_test("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", 6, 803711);
-    }
-
-    #[test]
-    fn movegen_perft_8() {
-        movegen_perft_test("r3k3/8/8/8/8/8/8/3K4 b q - 0 1", 6, 803711);
-    }
-
-    #[test]
-    fn movegen_perft_9() {
-        movegen_perft_test("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4, 1274206);
-    }
-
-    #[test]
-    fn movegen_perft_10() {
-        movegen_perft_test("r3k2r/7b/8/8/8/8/1B4BQ/R3K2R b KQkq - 0 1", 4, 1274206);
-    }
-
-    #[test]
-    fn movegen_perft_11() {
-        movegen_perft_test("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4, 1720476);
-    }
-
-    #[test]
-    fn movegen_perft_12() {
-        movegen_perft_test("r3k2r/8/5Q2/8/8/3q4/8/R3K2R w KQkq - 0 1", 4, 1720476);
-    }
-
-    #[test]
-    fn movegen_perft_13() {
-        movegen_perft_test("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", 6, 3821001);
-    }
-
-    #[test]
-    fn movegen_perft_14() {
-        movegen_perft_test("3K4/8/8/8/8/8/4p3/2k2R2 b - - 0 1", 6, 3821001);
-    }
-
-    #[test]
-    fn movegen_perft_15() {
-        movegen_perft_test("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5, 1004658);
-    }
-
-    #[test]
-    fn movegen_perft_16() {
-        movegen_perft_test("5K2/8/1Q6/2N5/8/1p2k3/8/8 w - - 0 1", 5, 1004658);
-    }
-
-    #[test]
-    fn movegen_perft_17() {
-        movegen_perft_test("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6, 217342);
-    }
-
-    #[test]
-    fn movegen_perft_18() {
-        movegen_perft_test("8/k7/8/8/8/8/1p6/4K3 b - - 0 1", 6, 217342);
-    }
-
-    #[test]
-    fn movegen_perft_19() {
-        movegen_perft_test("8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92683);
-    }
-
-    #[test]
-    fn movegen_perft_20() {
-        movegen_perft_test("8/8/8/8/8/k7/p1K5/8 b - - 0 1", 6, 92683);
-    }
-
-    #[test]
-    fn movegen_perft_21() {
-        movegen_perft_test("K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2217);
-    }
-
-    #[test]
-    fn movegen_perft_22() {
-        movegen_perft_test("8/8/8/8/8/p7/8/k1K5 b - - 0 1", 6, 2217);
-    }
-
-    #[test]
-    fn movegen_perft_23() {
-        movegen_perft_test("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7, 567584);
+    fn movegen_perft_in_place() {
+        for (fen, depth, result) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                5,
+                4865609,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                4,
+                4085603,
+            ),
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1440467),
+        ] {
+            let mut board: Board = fen.parse().unwrap();
+            assert_eq!(MoveGen::perft_test_in_place(&mut board, depth), result);
+            assert_eq!(format!("{}", board), fen);
+        }
     }
 
     #[test]
-    fn movegen_perft_24() {
-        movegen_perft_test("8/8/8/8/1k6/8/K1p5/8 b - - 0 1", 7, 567584);
-    }
+    fn movegen_perft_divide() {
+        let board = Board::default();
+        let divide = MoveGen::perft_divide(&board, 3);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<usize>(), 8902);
+        for (m, nodes) in [("a2a3", 380), ("e2e4", 600), ("g1f3", 440), ("b1c3", 440)] {
+            assert!(
+                divide
+                    .iter()
+                    .any(|(dm, dn)| dm.to_string() == m && *dn == nodes)
+            );
+        }
 
-    #[test]
-    fn movegen_perft_25() {
-        movegen_perft_test("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23527);
+        let board: Board = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        let divide = MoveGen::perft_divide(&board, 1);
+        assert_eq!(divide.len(), 48);
+        assert!(divide.iter().all(|(_, nodes)| *nodes == 1));
+        assert!(MoveGen::perft_divide(&board, 0).is_empty());
+        assert_eq!(
+            MoveGen::perft_divide(&board, 3)
+                .iter()
+                .map(|(_, nodes)| nodes)
+                .sum::<usize>(),
+            97862
+        );
     }
 
     #[test]
-    fn movegen_perft_26() {
-        movegen_perft_test("8/5k2/8/5N2/5Q2/2K5/8/8 w - - 0 1", 4, 23527);
+    fn movegen_max_movents() {
+        let fen = "R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1";
+        let board: Board = fen.parse().unwrap();
+        let movements = MoveGen::new_legal(&board);
+        assert_eq!(movements.len(), 218);
     }
 }
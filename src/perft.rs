@@ -0,0 +1,780 @@
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::file::File;
+use crate::movegen::MoveGen;
+use crate::pieces::Piece;
+
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Number of tasks per thread that [`perft_parallel`] aims for, so that threads running
+/// out of work early can pick up the tasks left by the others.
+const TASKS_PER_THREAD: usize = 16;
+
+/// Counts the leaf nodes at `depth` like [`MoveGen::perft_test`], spread over `threads`
+/// threads.
+///
+/// The tree is expanded a few plies deep into a queue of positions, and each thread keeps
+/// taking the next position from the queue until it is empty, so the counts are the same
+/// as the serial version whatever the number of threads.
+pub fn perft_parallel(board: &Board, depth: usize, threads: usize) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    if threads <= 1 || depth <= 2 {
+        return MoveGen::perft_test(board, depth);
+    }
+
+    let mut tasks = vec![board.clone()];
+    let mut task_depth = depth;
+    while tasks.len() < threads * TASKS_PER_THREAD && task_depth > 2 {
+        tasks = tasks
+            .iter()
+            .flat_map(|board| MoveGen::new_legal(board).map(|m| board.make_move(m)))
+            .collect();
+        task_depth -= 1;
+    }
+
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(tasks.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut nodes = 0;
+                    while let Some(board) = tasks.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        nodes += MoveGen::perft_test(board, task_depth);
+                    }
+                    nodes
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    })
+}
+
+/// An entry of a [`PerftTable`]: the node count of a position at some depth.
+#[derive(Clone, Copy, Default)]
+struct PerftEntry {
+    key: u64,
+    count: u64,
+    depth: u32,
+}
+
+/// A fixed-size table of perft counts keyed by the zobrist hash of the position,
+/// letting [`perft_hashed`] skip the subtrees it has already counted.
+///
+/// Each position has a single slot, and a new entry always replaces the old one.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    /// Creates a table using at most `megabytes` of memory, with at least one entry.
+    pub fn new(megabytes: usize) -> Self {
+        let budget = megabytes * 1024 * 1024 / std::mem::size_of::<PerftEntry>();
+        // a power of two, so the index is just the low bits of the key
+        let len = if budget == 0 { 1 } else { 1 << budget.ilog2() };
+        Self {
+            entries: vec![PerftEntry::default(); len],
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline(always)]
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    fn get(&self, key: u64, depth: usize) -> Option<usize> {
+        let entry = self.entries[self.index(key)];
+        (entry.key == key && entry.depth as usize == depth).then_some(entry.count as usize)
+    }
+
+    fn insert(&mut self, key: u64, depth: usize, count: usize) {
+        let idx = self.index(key);
+        self.entries[idx] = PerftEntry {
+            key,
+            count: count as u64,
+            depth: depth as u32,
+        };
+    }
+}
+
+/// Counts the leaf nodes at `depth` like [`MoveGen::perft_test`], looking up and storing
+/// the count of every position below the root in `table`.
+///
+/// The table can be reused between calls on the same or other positions.
+pub fn perft_hashed(board: &Board, depth: usize, table: &mut PerftTable) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    let movements = MoveGen::new_legal(board);
+    if depth == 1 {
+        return movements.len();
+    }
+    if let Some(count) = table.get(board.get_hash(), depth) {
+        return count;
+    }
+
+    let mut result = 0;
+    for m in movements {
+        result += perft_hashed(&board.make_move(m), depth - 1, table);
+    }
+    table.insert(board.get_hash(), depth, result);
+    result
+}
+
+/// Breakdown of the leaf nodes of a perft by the move leading to them, as found in
+/// published perft tables.
+///
+/// Captures include en passant captures. A check is discovered when it is given only by
+/// a piece other than the one that moved, counting both the king and the rook as moved
+/// when castling; double checks are counted apart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passant: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub discovered_checks: u64,
+    pub double_checks: u64,
+    pub checkmates: u64,
+}
+
+impl PerftStats {
+    fn add(&mut self, other: &PerftStats) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passant += other.en_passant;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.discovered_checks += other.discovered_checks;
+        self.double_checks += other.double_checks;
+        self.checkmates += other.checkmates;
+    }
+
+    /// Counts the leaf reached by playing `m` on `board`.
+    fn count_leaf(&mut self, board: &Board, m: ChessMove) {
+        let side = board.side_to_move();
+        let piece = board.get_piece(m.source).unwrap();
+        let castled = piece == Piece::King && board.is_castle(m);
+        let en_passant = piece == Piece::Pawn && board.en_passant() == Some(m.dest);
+        let child = board.make_move(m);
+
+        self.nodes += 1;
+        if en_passant {
+            self.en_passant += 1;
+            self.captures += 1;
+        } else if !castled
+            && !(board.get_color_bitboard(!side) & BitBoard::from_square(m.dest)).is_empty()
+        {
+            self.captures += 1;
+        }
+        if castled {
+            self.castles += 1;
+        }
+        if m.promotion.is_some() {
+            self.promotions += 1;
+        }
+
+        let checkers = child.get_checkers_bitboard();
+        if checkers.is_empty() {
+            return;
+        }
+        self.checks += 1;
+
+        let moved = if castled {
+            let (king_dest, rook_dest) =
+                if m.dest.get_file().to_index() > m.source.get_file().to_index() {
+                    (File::G, File::F)
+                } else {
+                    (File::C, File::D)
+                };
+            let rank = side.starting_rank();
+            BitBoard::set(rank, king_dest) | BitBoard::set(rank, rook_dest)
+        } else {
+            BitBoard::from_square(m.dest)
+        };
+        if checkers.0.count_ones() > 1 {
+            self.double_checks += 1;
+        } else if !(checkers & !moved).is_empty() {
+            self.discovered_checks += 1;
+        }
+        if MoveGen::new_legal(&child).len() == 0 {
+            self.checkmates += 1;
+        }
+    }
+}
+
+/// Counts the leaf nodes at `depth` like [`MoveGen::perft_test`], broken down by the kind
+/// of the last move played and the check it gives.
+pub fn perft_stats(board: &Board, depth: usize) -> PerftStats {
+    let mut stats = PerftStats::default();
+    if depth == 0 {
+        stats.nodes = 1;
+    } else if depth == 1 {
+        for m in MoveGen::new_legal(board) {
+            stats.count_leaf(board, m);
+        }
+    } else {
+        for m in MoveGen::new_legal(board) {
+            stats.add(&perft_stats(&board.make_move(m), depth - 1));
+        }
+    }
+    stats
+}
+
+/// Node counts below each root move, keyed by the move in UCI notation.
+pub type Divide = HashMap<String, usize>;
+
+/// Reads divide output in the format of Stockfish's `go perft` (`e2e4: 600`),
+/// ignoring every other line.
+pub fn parse_divide(text: &str) -> Divide {
+    text.lines()
+        .filter_map(|line| {
+            let (m, nodes) = line.trim().split_once(':')?;
+            ChessMove::from_str(m.trim()).ok()?;
+            Some((m.trim().to_string(), nodes.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Somewhere to get trusted divide counts from, usually another engine.
+pub trait ReferenceSource {
+    /// Returns the divide counts of `board` at `depth`, or `None` if they are not known.
+    fn divide(&mut self, board: &Board, depth: usize) -> Result<Option<Divide>>;
+}
+
+/// Divide output saved to a file, possibly for several positions.
+///
+/// Lines before any header hold the counts of the root position. A line
+/// `fen <FEN>`, `position fen <FEN> [moves ...]` or `position startpos [moves ...]`
+/// starts the counts of another position, so a whole debugging session can be
+/// replayed from a single file.
+pub struct DivideFile {
+    blocks: HashMap<u64, Divide>,
+}
+
+impl DivideFile {
+    pub fn new(root: &Board, text: &str) -> Result<Self> {
+        let mut blocks: HashMap<u64, String> = HashMap::new();
+        let mut current = root.get_hash();
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("fen ") || trimmed.starts_with("position ") {
+                current = parse_position(trimmed)?.get_hash();
+            } else {
+                let block = blocks.entry(current).or_default();
+                block.push_str(line);
+                block.push('\n');
+            }
+        }
+
+        Ok(Self {
+            blocks: blocks
+                .into_iter()
+                .map(|(hash, text)| (hash, parse_divide(&text)))
+                .collect(),
+        })
+    }
+}
+
+impl ReferenceSource for DivideFile {
+    fn divide(&mut self, board: &Board, _depth: usize) -> Result<Option<Divide>> {
+        Ok(self.blocks.get(&board.get_hash()).cloned())
+    }
+}
+
+/// Parses a `fen <FEN>` or UCI `position` line into a board.
+fn parse_position(line: &str) -> Result<Board> {
+    let mut tokens = line.split_whitespace();
+    let mut board = match (tokens.next(), tokens.next()) {
+        (Some("fen"), Some(first)) => {
+            let fen: Vec<&str> = std::iter::once(first).chain(tokens.by_ref()).collect();
+            return Ok(Board::from_str_lenient(&fen.join(" "))?);
+        }
+        (Some("position"), Some("startpos")) => Board::default(),
+        (Some("position"), Some("fen")) => {
+            let fen: Vec<&str> = tokens.by_ref().take_while(|t| *t != "moves").collect();
+            Board::from_str_lenient(&fen.join(" "))?
+        }
+        _ => bail!("invalid position line '{}'", line),
+    };
+
+    for uci in tokens.skip_while(|t| *t == "moves") {
+        board = board.make_move(board.parse_uci_move(uci)?);
+    }
+    Ok(board)
+}
+
+/// A UCI engine supporting `go perft`, like Stockfish, run as a child process.
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciEngine {
+    /// Starts the engine from a command line, split on whitespace.
+    pub fn new(command: &str) -> Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().context("empty engine command")?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to start engine '{}'", command))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut engine = Self {
+            child,
+            stdin,
+            stdout,
+        };
+        engine.send("uci")?;
+        engine.read_until("uciok")?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Reads the engine output up to a line starting with `prefix`, returning it all.
+    fn read_until(&mut self, prefix: &str) -> Result<String> {
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                bail!("engine exited before sending '{}'", prefix);
+            }
+            output.push_str(&line);
+            if line.trim_start().starts_with(prefix) {
+                return Ok(output);
+            }
+        }
+    }
+}
+
+impl ReferenceSource for UciEngine {
+    fn divide(&mut self, board: &Board, depth: usize) -> Result<Option<Divide>> {
+        self.send(&format!(
+            "setoption name UCI_Chess960 value {}",
+            board.is_chess960()
+        ))?;
+        self.send(&format!("position fen {}", board))?;
+        self.send(&format!("go perft {}", depth))?;
+        let output = self.read_until("Nodes searched")?;
+        Ok(Some(parse_divide(&output)))
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+/// What [`find_divergence`] found at the end of its walk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// Every count agrees with the reference.
+    Match,
+    /// The reference has a move that the move generator doesn't.
+    MissingMove(String),
+    /// The move generator has a move that the reference doesn't.
+    ExtraMove(ChessMove),
+    /// The counts differ, but the reference has no counts for the next position.
+    NoReference { depth: usize },
+    /// The reference counts `expected` nodes below the move but the move generator
+    /// counts `found`, and no move further down explains the difference.
+    CountMismatch {
+        chess_move: ChessMove,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// The position where the move generator and the reference disagree.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub board: Board,
+    /// The moves leading from the root to `board`.
+    pub path: Vec<ChessMove>,
+    pub kind: DivergenceKind,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|m| m.to_string()).collect();
+        match &self.kind {
+            DivergenceKind::Match => return write!(f, "all counts match the reference"),
+            DivergenceKind::MissingMove(m) => write!(f, "missing move {}", m)?,
+            DivergenceKind::ExtraMove(m) => write!(f, "extra move {}", m)?,
+            DivergenceKind::NoReference { depth } => {
+                write!(f, "counts differ, no reference for depth {}", depth)?
+            }
+            DivergenceKind::CountMismatch {
+                chess_move,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} has {} nodes instead of {}",
+                chess_move, found, expected
+            )?,
+        }
+        write!(f, " in position {}", self.board)?;
+        if !path.is_empty() {
+            write!(f, " (after {})", path.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the perft divide of `board` with `reference`, walking down into the first
+/// root move whose count differs until the exact missing or extra move is found.
+///
+/// When the counts below a move all agree although the total for the move didn't, or
+/// the move is a leaf, the divergence is reported as a count mismatch of that move.
+/// Fails if `depth` is 0, as there is no divide to compare.
+pub fn find_divergence(
+    board: &Board,
+    mut depth: usize,
+    reference: &mut dyn ReferenceSource,
+) -> Result<Divergence> {
+    if depth == 0 {
+        bail!("divergence search needs a depth of at least 1");
+    }
+    let mut board = board.clone();
+    let mut path = Vec::new();
+    // the position, the move and the counts of the last mismatch walked into
+    let mut parent: Option<(Board, DivergenceKind)> = None;
+
+    loop {
+        let ours = MoveGen::perft_divide(&board, depth);
+        let Some(theirs) = reference.divide(&board, depth)? else {
+            return Ok(Divergence {
+                board,
+                path,
+                kind: DivergenceKind::NoReference { depth },
+            });
+        };
+
+        let kind = if let Some((m, _)) = ours
+            .iter()
+            .find(|(m, _)| !theirs.contains_key(&m.to_string()))
+        {
+            Some(DivergenceKind::ExtraMove(*m))
+        } else {
+            let mut missing: Vec<&String> = theirs
+                .keys()
+                .filter(|m| !ours.iter().any(|(ours, _)| ours.to_string() == **m))
+                .collect();
+            missing.sort();
+            missing
+                .first()
+                .map(|m| DivergenceKind::MissingMove(m.to_string()))
+        };
+        if let Some(kind) = kind {
+            return Ok(Divergence { board, path, kind });
+        }
+
+        let mismatch = ours.iter().find_map(|&(m, found)| {
+            let expected = theirs[&m.to_string()];
+            (expected != found).then_some(DivergenceKind::CountMismatch {
+                chess_move: m,
+                expected,
+                found,
+            })
+        });
+        match mismatch {
+            // a move at depth 1 has no moves below it to blame
+            Some(kind) if depth == 1 => return Ok(Divergence { board, path, kind }),
+            Some(kind) => {
+                if let DivergenceKind::CountMismatch { chess_move, .. } = kind {
+                    let next = board.make_move(chess_move);
+                    parent = Some((std::mem::replace(&mut board, next), kind));
+                    path.push(chess_move);
+                    depth -= 1;
+                }
+            }
+            None => {
+                let Some((board, kind)) = parent else {
+                    return Ok(Divergence {
+                        board,
+                        path,
+                        kind: DivergenceKind::Match,
+                    });
+                };
+                path.pop();
+                return Ok(Divergence { board, path, kind });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn divide_text(board: &Board, depth: usize) -> String {
+        MoveGen::perft_divide(board, depth)
+            .iter()
+            .map(|(m, nodes)| format!("{}: {}\n", m, nodes))
+            .collect()
+    }
+
+    #[test]
+    fn test_perft_parallel() {
+        for (fen, depth) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                5,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                4,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", 3),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let expected = MoveGen::perft_test(&board, depth);
+            for threads in [1, 2, 3, 8] {
+                assert_eq!(perft_parallel(&board, depth, threads), expected, "{}", fen);
+            }
+        }
+        assert_eq!(perft_parallel(&Board::default(), 0, 4), 1);
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        for (fen, depth) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                5,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                4,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 6),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                4,
+            ),
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                4,
+            ),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let expected = MoveGen::perft_test(&board, depth);
+            // a tiny table forces collisions and replacements
+            for mut table in [PerftTable::new(0), PerftTable::new(1), PerftTable::new(16)] {
+                assert_eq!(perft_hashed(&board, depth, &mut table), expected, "{}", fen);
+                assert_eq!(perft_hashed(&board, depth, &mut table), expected, "{}", fen);
+            }
+        }
+        assert_eq!(
+            perft_hashed(&Board::default(), 0, &mut PerftTable::new(1)),
+            1
+        );
+        assert_eq!(PerftTable::new(0).len(), 1);
+        assert!(PerftTable::new(1).len().is_power_of_two());
+    }
+
+    fn stats(counts: [u64; 9]) -> PerftStats {
+        let [
+            nodes,
+            captures,
+            en_passant,
+            castles,
+            promotions,
+            checks,
+            discovered_checks,
+            double_checks,
+            checkmates,
+        ] = counts;
+        PerftStats {
+            nodes,
+            captures,
+            en_passant,
+            castles,
+            promotions,
+            checks,
+            discovered_checks,
+            double_checks,
+            checkmates,
+        }
+    }
+
+    #[test]
+    fn test_perft_stats() {
+        // nodes, captures, e.p., castles, promotions, checks, discovered, double, mates
+        let tables: [(&str, &[[u64; 9]]); 4] = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[
+                    [20, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [400, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [8902, 34, 0, 0, 0, 12, 0, 0, 0],
+                    [197281, 1576, 0, 0, 0, 469, 0, 0, 8],
+                    [4865609, 82719, 258, 0, 0, 27351, 6, 0, 347],
+                ],
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[
+                    [48, 8, 0, 2, 0, 0, 0, 0, 0],
+                    [2039, 351, 1, 91, 0, 3, 0, 0, 0],
+                    [97862, 17102, 45, 3162, 0, 993, 0, 0, 1],
+                    [4085603, 757163, 1929, 128013, 15172, 25523, 42, 6, 43],
+                ],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                &[
+                    [14, 1, 0, 0, 0, 2, 0, 0, 0],
+                    [191, 14, 0, 0, 0, 10, 0, 0, 0],
+                    [2812, 209, 2, 0, 0, 267, 3, 0, 0],
+                    [43238, 3348, 123, 0, 0, 1680, 106, 0, 17],
+                    [674624, 52051, 1165, 0, 0, 52950, 1292, 3, 0],
+                ],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                &[
+                    [6, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [264, 87, 0, 6, 48, 10, 0, 0, 0],
+                    [9467, 1021, 4, 0, 120, 38, 2, 0, 22],
+                    [422333, 131393, 0, 7795, 60032, 15492, 19, 0, 5],
+                ],
+            ),
+        ];
+        for (fen, table) in tables {
+            let board = Board::from_str(fen).unwrap();
+            for (depth, counts) in table.iter().enumerate() {
+                assert_eq!(perft_stats(&board, depth + 1), stats(*counts), "{}", fen);
+            }
+        }
+
+        // positions 5 and 6 only have node counts published
+        for (fen, nodes) in [
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                [44, 1486, 62379],
+            ),
+            (
+                "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                [46, 2079, 89890],
+            ),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            for (depth, nodes) in nodes.into_iter().enumerate() {
+                assert_eq!(perft_stats(&board, depth + 1).nodes, nodes, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_divide() {
+        let divide = parse_divide("info string hi\na2a3: 380\n e7e8q: 2\n\nNodes searched: 382\n");
+        assert_eq!(divide.len(), 2);
+        assert_eq!(divide["a2a3"], 380);
+        assert_eq!(divide["e7e8q"], 2);
+    }
+
+    #[test]
+    fn test_find_divergence() {
+        let board = Board::default();
+        let mut reference = DivideFile::new(&board, &divide_text(&board, 3)).unwrap();
+        let divergence = find_divergence(&board, 3, &mut reference).unwrap();
+        assert_eq!(divergence.kind, DivergenceKind::Match);
+
+        // a reference where a7a6 is not legal after 1. e4 and 1... Nf6
+        let after_e4 = board.make_move(board.parse_uci_move("e2e4").unwrap());
+        let after_nf6 = after_e4.make_move(after_e4.parse_uci_move("g8f6").unwrap());
+        let root = divide_text(&board, 3).replace("e2e4: 600", "e2e4: 599");
+        let child = divide_text(&after_e4, 2).replace("g8f6: 30", "g8f6: 29");
+        let text = format!(
+            "{}position startpos moves e2e4\n{}fen {}\nNodes searched: 1\n{}",
+            root,
+            child,
+            after_nf6,
+            divide_text(&after_nf6, 1)
+        );
+
+        // the counts below 1... Nf6 all agree, so the reference disagrees with itself
+        let mut reference = DivideFile::new(&board, &text).unwrap();
+        let divergence = find_divergence(&board, 3, &mut reference).unwrap();
+        assert_eq!(divergence.board.to_string(), after_e4.to_string());
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::CountMismatch {
+                chess_move: after_e4.parse_uci_move("g8f6").unwrap(),
+                expected: 29,
+                found: 30
+            }
+        );
+
+        let leaf = divide_text(&board, 1).replace("e2e4: 1", "e2e4: 2");
+        let mut reference = DivideFile::new(&board, &leaf).unwrap();
+        let divergence = find_divergence(&board, 1, &mut reference).unwrap();
+        assert!(divergence.path.is_empty());
+        assert_eq!(
+            divergence.to_string(),
+            format!("e2e4 has 1 nodes instead of 2 in position {}", board)
+        );
+
+        let mut reference = DivideFile::new(&board, &text.replace("a2a3: 1\n", "")).unwrap();
+        let divergence = find_divergence(&board, 3, &mut reference).unwrap();
+        assert_eq!(divergence.path.len(), 2);
+        assert_eq!(divergence.board.to_string(), after_nf6.to_string());
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::ExtraMove(after_nf6.parse_uci_move("a2a3").unwrap())
+        );
+
+        let mut reference =
+            DivideFile::new(&board, &text.replace("a2a3: 1\n", "a2a3: 1\na2a5: 1\n")).unwrap();
+        let divergence = find_divergence(&board, 3, &mut reference).unwrap();
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::MissingMove("a2a5".to_string())
+        );
+
+        let mut reference = DivideFile::new(&board, &root).unwrap();
+        let divergence = find_divergence(&board, 3, &mut reference).unwrap();
+        assert_eq!(divergence.kind, DivergenceKind::NoReference { depth: 2 });
+        assert_eq!(divergence.path, [board.parse_uci_move("e2e4").unwrap()]);
+
+        assert!(find_divergence(&board, 0, &mut reference).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+pub mod reader;
+pub mod writer;
+
+pub use reader::PgnReader;
+
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::game::GameResult;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The tags every PGN game must have, in the order they are exported.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// A move of a PGN game, with its annotations and the variations that replace it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    pub chess_move: ChessMove,
+    /// The move in Standard Algebraic Notation, as produced by `Board::san`.
+    pub san: String,
+    /// Numeric Annotation Glyphs, with `!`, `?` and friends turned into `$1` to `$6`.
+    pub nags: Vec<u8>,
+    /// Comments before the move, only found at the start of the game or of a variation.
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    /// Alternative lines, each starting from the position before this move.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    #[inline(always)]
+    pub fn new(chess_move: ChessMove, san: String) -> Self {
+        Self {
+            chess_move,
+            san,
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// A game read from PGN: its tags, in file order, and the tree of moves.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    /// Comments of a game without moves.
+    pub comments: Vec<String>,
+    /// The game termination marker, `None` for `*`.
+    pub result: Option<GameResult>,
+}
+
+impl PgnGame {
+    /// Returns the value of the tag `name`, if present.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the starting position, read from the `FEN` tag when there is one.
+    pub fn start_board(&self) -> anyhow::Result<Board> {
+        match self.tag("FEN") {
+            Some(fen) => Ok(Board::from_str(fen)?),
+            None => Ok(Board::default()),
+        }
+    }
+
+    /// Returns the moves of the main line.
+    pub fn mainline(&self) -> impl Iterator<Item = ChessMove> + '_ {
+        self.moves.iter().map(|m| m.chess_move)
+    }
+}
+
+/// Error returned when a game can't be read, pointing at where the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for PgnError {}
+
+/// Returns the PGN termination marker of `result`.
+pub fn result_to_str(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins) => "1-0",
+        Some(GameResult::BlackWins) => "0-1",
+        Some(GameResult::Draw) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Parses a PGN termination marker, with `*` giving `Some(None)`.
+pub fn result_from_str(s: &str) -> Option<Option<GameResult>> {
+    match s {
+        "1-0" => Some(Some(GameResult::WhiteWins)),
+        "0-1" => Some(Some(GameResult::BlackWins)),
+        "1/2-1/2" => Some(Some(GameResult::Draw)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
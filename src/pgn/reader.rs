@@ -0,0 +1,635 @@
+use super::{PgnError, PgnGame, PgnMove, result_from_str};
+use crate::board::Board;
+use crate::game::GameResult;
+
+use std::io::BufRead;
+use std::mem;
+use std::str::FromStr;
+
+/// Reads PGN games one at a time from any buffered source, so that large
+/// collections never have to fit in memory.
+///
+/// A malformed game yields a [`PgnError`] and the reader moves on to the next one.
+/// An IO error is yielded once and ends the iteration.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    line: usize,
+    pending: Option<String>,
+    done: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    #[inline(always)]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            pending: None,
+            done: false,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, PgnError> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                self.line += 1;
+                if self.line == 1 {
+                    line = line.trim_start_matches('\u{feff}').to_string();
+                }
+                Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+            }
+            Err(err) => Err(PgnError {
+                line: self.line + 1,
+                column: 1,
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// Collects the lines of the next game: its tag pairs and its movetext, which ends
+    /// where a tag pair starts outside of a comment.
+    fn read_game_lines(&mut self) -> Result<Option<(usize, Vec<String>)>, PgnError> {
+        let mut lines = Vec::new();
+        let mut first_line = 0;
+        let mut in_movetext = false;
+        let mut in_comment = false;
+
+        while let Some(line) = self.read_line()? {
+            let trimmed = line.trim();
+            if line.starts_with('%') || (lines.is_empty() && trimmed.is_empty()) {
+                continue;
+            }
+            if !in_comment && in_movetext && trimmed.starts_with('[') {
+                self.pending = Some(line);
+                break;
+            }
+
+            if lines.is_empty() {
+                first_line = self.line;
+            }
+            if !in_comment && !trimmed.is_empty() && !trimmed.starts_with('[') {
+                in_movetext = true;
+            }
+
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    // tag values may hold braces and semicolons
+                    '"' if !in_comment => {
+                        while let Some(c) = chars.next() {
+                            match c {
+                                '\\' => {
+                                    chars.next();
+                                }
+                                '"' => break,
+                                _ => {}
+                            }
+                        }
+                    }
+                    '{' => in_comment = true,
+                    '}' => in_comment = false,
+                    ';' if !in_comment => break,
+                    _ => {}
+                }
+            }
+            lines.push(line);
+        }
+
+        if lines.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((first_line, lines)))
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_game_lines() {
+            Ok(Some((first_line, lines))) => Some(parse_game(first_line, &lines)),
+            Ok(None) => None,
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    /// Parses a single game, ignoring anything after it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PgnReader::new(s.as_bytes()).next().unwrap_or(Err(PgnError {
+            line: 1,
+            column: 1,
+            message: "no game found".to_string(),
+        }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    Period,
+    Asterisk,
+    String(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+}
+
+struct Lexer {
+    chars: Vec<(usize, usize, char)>,
+    pos: usize,
+}
+
+impl Lexer {
+    fn new(first_line: usize, lines: &[String]) -> Self {
+        let mut chars = Vec::new();
+        for (idx, line) in lines.iter().enumerate() {
+            let mut column = 0;
+            for (col, c) in line.chars().enumerate() {
+                chars.push((first_line + idx, col + 1, c));
+                column = col + 1;
+            }
+            chars.push((first_line + idx, column + 1, '\n'));
+        }
+        Self { chars, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|&(_, _, c)| c)
+    }
+
+    fn error(&self, (line, column): (usize, usize), message: String) -> PgnError {
+        PgnError {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize, usize)>, PgnError> {
+        let mut tokens = Vec::new();
+
+        while let Some(&(line, column, c)) = self.chars.get(self.pos) {
+            self.pos += 1;
+            let token = match c {
+                c if c.is_whitespace() => continue,
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                '.' => Token::Period,
+                '*' => Token::Asterisk,
+                ';' => {
+                    let mut text = String::new();
+                    while let Some(c) = self.peek().filter(|&c| c != '\n') {
+                        text.push(c);
+                        self.pos += 1;
+                    }
+                    Token::Comment(text.trim().to_string())
+                }
+                '{' => {
+                    let mut text = String::new();
+                    loop {
+                        match self.peek() {
+                            Some('}') => break,
+                            Some(c) => text.push(c),
+                            None => {
+                                return Err(
+                                    self.error((line, column), "unterminated comment".to_string())
+                                );
+                            }
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                    Token::Comment(text.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                '"' => {
+                    let mut text = String::new();
+                    loop {
+                        match self.peek() {
+                            Some('"') => break,
+                            Some('\\')
+                                if matches!(
+                                    self.chars.get(self.pos + 1),
+                                    Some((_, _, '"' | '\\'))
+                                ) =>
+                            {
+                                self.pos += 1;
+                                text.push(self.peek().unwrap());
+                            }
+                            Some('\n') | None => {
+                                return Err(
+                                    self.error((line, column), "unterminated string".to_string())
+                                );
+                            }
+                            Some(c) => text.push(c),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                    Token::String(text)
+                }
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        self.pos += 1;
+                    }
+                    match digits.parse() {
+                        Ok(nag) => Token::Nag(nag),
+                        Err(_) => {
+                            return Err(
+                                self.error((line, column), format!("invalid NAG '${}'", digits))
+                            );
+                        }
+                    }
+                }
+                '!' | '?' => {
+                    let mut suffix = c.to_string();
+                    while let Some(c) = self.peek().filter(|&c| c == '!' || c == '?') {
+                        suffix.push(c);
+                        self.pos += 1;
+                    }
+                    let nag = match suffix.as_str() {
+                        "!" => 1,
+                        "?" => 2,
+                        "!!" => 3,
+                        "??" => 4,
+                        "!?" => 5,
+                        "?!" => 6,
+                        _ => {
+                            return Err(self.error(
+                                (line, column),
+                                format!("invalid annotation '{}'", suffix),
+                            ));
+                        }
+                    };
+                    Token::Nag(nag)
+                }
+                '<' => {
+                    // reserved for future expansion
+                    while self.peek().is_some_and(|c| c != '>') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                    continue;
+                }
+                c if c.is_ascii_alphanumeric() => {
+                    let mut symbol = c.to_string();
+                    while let Some(c) = self
+                        .peek()
+                        .filter(|c| c.is_ascii_alphanumeric() || "_+#=:-/".contains(*c))
+                    {
+                        symbol.push(c);
+                        self.pos += 1;
+                    }
+                    Token::Symbol(symbol)
+                }
+                _ => {
+                    return Err(self.error((line, column), format!("unexpected character '{}'", c)));
+                }
+            };
+            tokens.push((token, line, column));
+        }
+
+        Ok(tokens)
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+    end: (usize, usize),
+    result: Option<GameResult>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Token, usize, usize)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error_at(&self, pos: usize, message: String) -> PgnError {
+        let (line, column) = self
+            .tokens
+            .get(pos)
+            .map(|&(_, line, column)| (line, column))
+            .unwrap_or(self.end);
+        PgnError {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn parse_tags(&mut self) -> Result<Vec<(String, String)>, PgnError> {
+        let mut tags = Vec::new();
+        while self.tokens.get(self.pos).map(|(token, _, _)| token) == Some(&Token::LeftBracket) {
+            let start = self.pos;
+            self.pos += 1;
+            let name = match self.next() {
+                Some((Token::Symbol(name), _, _)) => name,
+                _ => return Err(self.error_at(self.pos - 1, "expected a tag name".to_string())),
+            };
+            let value = match self.next() {
+                Some((Token::String(value), _, _)) => value,
+                _ => return Err(self.error_at(self.pos - 1, "expected a tag value".to_string())),
+            };
+            if !matches!(self.next(), Some((Token::RightBracket, _, _))) {
+                return Err(self.error_at(self.pos - 1, "expected ']'".to_string()));
+            }
+            if tags.iter().any(|(tag, _)| *tag == name) {
+                return Err(self.error_at(start, format!("duplicate tag '{}'", name)));
+            }
+            tags.push((name, value));
+        }
+        Ok(tags)
+    }
+
+    /// Parses moves from `board` until the end of the game, or until the closing
+    /// parenthesis of a variation when `nested` is set. Comments that come before
+    /// any move are returned apart.
+    fn parse_line(
+        &mut self,
+        mut board: Board,
+        nested: bool,
+    ) -> Result<(Vec<PgnMove>, Vec<String>), PgnError> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut comments = Vec::new();
+        let mut previous = board.clone();
+
+        loop {
+            let pos = self.pos;
+            let Some((token, _, _)) = self.next() else {
+                if nested {
+                    return Err(self.error_at(pos, "unterminated variation".to_string()));
+                }
+                break;
+            };
+
+            match token {
+                Token::Period => {}
+                Token::Symbol(symbol) if symbol.chars().all(|c| c.is_ascii_digit()) => {}
+                Token::Asterisk => self.parse_result(pos, nested, None)?,
+                Token::Symbol(symbol) if result_from_str(&symbol).is_some() => {
+                    self.parse_result(pos, nested, result_from_str(&symbol).unwrap())?
+                }
+                Token::Symbol(symbol) => {
+                    let m = board
+                        .parse_san(&symbol)
+                        .map_err(|err| self.error_at(pos, err.to_string()))?;
+                    let mut pgn_move = PgnMove::new(m, board.san(m));
+                    pgn_move.comments_before = mem::take(&mut comments);
+                    moves.push(pgn_move);
+                    previous = board.clone();
+                    board.make_move_in_place(m);
+                }
+                Token::Nag(nag) => match moves.last_mut() {
+                    Some(last) => last.nags.push(nag),
+                    None => {
+                        return Err(self.error_at(pos, "annotation before any move".to_string()));
+                    }
+                },
+                Token::Comment(comment) => match moves.last_mut() {
+                    Some(last) => last.comments.push(comment),
+                    None => comments.push(comment),
+                },
+                Token::LeftParen => {
+                    if moves.is_empty() {
+                        return Err(self.error_at(pos, "variation before any move".to_string()));
+                    }
+                    let (variation, _) = self.parse_line(previous.clone(), true)?;
+                    if variation.is_empty() {
+                        return Err(self.error_at(pos, "empty variation".to_string()));
+                    }
+                    moves.last_mut().unwrap().variations.push(variation);
+                }
+                Token::RightParen if nested => break,
+                Token::RightParen => return Err(self.error_at(pos, "unmatched ')'".to_string())),
+                Token::LeftBracket | Token::RightBracket | Token::String(_) => {
+                    return Err(self.error_at(pos, "unexpected token in movetext".to_string()));
+                }
+            }
+        }
+
+        Ok((moves, comments))
+    }
+
+    fn parse_result(
+        &mut self,
+        pos: usize,
+        nested: bool,
+        result: Option<GameResult>,
+    ) -> Result<(), PgnError> {
+        if nested {
+            return Err(self.error_at(pos, "game result inside a variation".to_string()));
+        }
+        if self.pos < self.tokens.len() {
+            return Err(self.error_at(
+                self.pos,
+                "unexpected token after the game result".to_string(),
+            ));
+        }
+        self.result = result;
+        Ok(())
+    }
+}
+
+fn parse_game(first_line: usize, lines: &[String]) -> Result<PgnGame, PgnError> {
+    let tokens = Lexer::new(first_line, lines).tokenize()?;
+    let end = (
+        first_line + lines.len() - 1,
+        lines.last().map_or(0, |line| line.chars().count()) + 1,
+    );
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end,
+        result: None,
+    };
+
+    let mut game = PgnGame {
+        tags: parser.parse_tags()?,
+        ..Default::default()
+    };
+    let board = game
+        .start_board()
+        .map_err(|err| parser.error_at(0, format!("invalid FEN tag: {}", err)))?;
+
+    let (moves, comments) = parser.parse_line(board, false)?;
+    game.moves = moves;
+    game.comments = comments;
+    game.result = parser.result;
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_move::ChessMove;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "Nobody \"the\" Tester"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+[Event "Variations"]
+[Result "*"]
+
+{Start} 1. e4! (1. d4 d5 (1... Nf6 2. c4) 2. c4 $2) 1... e5?! ; rest of line
+2. Nf3 {multi
+line} Nc6 *
+
+[Event "Broken"]
+
+1. e4 e5 2. Ke3 *
+
+[Event "From FEN"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/4K2R w K - 0 1"]
+
+1. O-O Kd7 1-0
+"#;
+
+    #[test]
+    fn test_pgn_reader() {
+        let games: Vec<_> = PgnReader::new(GAMES.as_bytes()).collect();
+        assert_eq!(games.len(), 4);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tags.len(), 8);
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Annotator"), Some("Nobody \"the\" Tester"));
+        assert_eq!(game.moves.len(), 85);
+        assert_eq!(game.result, Some(GameResult::Draw));
+        assert_eq!(
+            game.moves[4].comments,
+            vec!["This opening is called the Ruy Lopez.".to_string()]
+        );
+        assert_eq!(game.moves[8].san, "O-O");
+        let mut board = game.start_board().unwrap();
+        for m in game.mainline() {
+            board = board.try_make_move(m).unwrap();
+        }
+        assert_eq!(
+            format!("{}", board),
+            "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43"
+        );
+
+        let game = games[1].as_ref().unwrap();
+        assert_eq!(game.result, None);
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.moves[0].comments_before, vec!["Start".to_string()]);
+        assert_eq!(game.moves[0].nags, vec![1]);
+        assert_eq!(game.moves[1].nags, vec![6]);
+        assert_eq!(game.moves[1].comments, vec!["rest of line".to_string()]);
+        assert_eq!(game.moves[2].comments, vec!["multi line".to_string()]);
+
+        let variation = &game.moves[0].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[2].san, "c4");
+        assert_eq!(variation[2].nags, vec![2]);
+        let sub_variation = &variation[1].variations[0];
+        assert_eq!(
+            sub_variation
+                .iter()
+                .map(|m| m.san.as_str())
+                .collect::<Vec<_>>(),
+            ["Nf6", "c4"]
+        );
+
+        let error = games[2].as_ref().unwrap_err();
+        assert_eq!((error.line, error.column), (28, 13));
+        assert!(error.message.contains("Ke3"));
+
+        let game = games[3].as_ref().unwrap();
+        assert_eq!(game.result, Some(GameResult::WhiteWins));
+        assert_eq!(
+            game.moves[0].chess_move,
+            ChessMove::new("e1".parse().unwrap(), "g1".parse().unwrap(), None)
+        );
+    }
+
+    #[test]
+    fn test_pgn_reader_tag_strings() {
+        let pgn = "[Event \"a{b\"]\n[Site \"c;d\"]\n[Annotator \"e \\\"{\\\" f\"]\n\n1. e4 *\n\n[Event \"g\"]\n\n1. d4 *\n";
+        let games: Vec<_> = PgnReader::new(pgn.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("a{b"));
+        assert_eq!(games[0].tag("Site"), Some("c;d"));
+        assert_eq!(games[0].tag("Annotator"), Some("e \"{\" f"));
+        assert_eq!(games[1].moves[0].san, "d4");
+    }
+
+    #[test]
+    fn test_pgn_reader_io_error() {
+        // invalid UTF-8 fails the read, and the reader stops there
+        let pgn = b"[Event \"a\"]\n\xff\n\n1. e4 *\n\n[Event \"b\"]\n\n1. d4 *\n";
+        let mut reader = PgnReader::new(&pgn[..]);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(reader.next().is_none());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_pgn_reader_errors() {
+        for (pgn, line, column) in [
+            ("1. e4 e5 2. Nf6 *", 1, 13),
+            ("[Event \"x]\n\n1. e4 *", 1, 8),
+            ("[Event x]\n\n1. e4 *", 1, 8),
+            ("1. e4 {unfinished", 1, 7),
+            ("1. e4 (e5) *", 1, 8),
+            ("1. e4 e5 ) *", 1, 10),
+            ("1. e4 (1. d4 *", 1, 14),
+            ("$1 1. e4 *", 1, 1),
+            ("1. e4 1-0 e5", 1, 11),
+            ("[FEN \"8/8 w - - 0 1\"]\n\n1. e4 *", 1, 1),
+        ] {
+            let error = PgnGame::from_str(pgn).expect_err(pgn);
+            assert_eq!(
+                (error.line, error.column),
+                (line, column),
+                "{}: {}",
+                pgn,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use super::{PgnGame, PgnMove, SEVEN_TAG_ROSTER, result_to_str};
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+
+use anyhow::Result;
+use std::fmt;
+
+/// Longest line of the movetext in export format.
+const MAX_LINE_LENGTH: usize = 80;
+
+impl PgnGame {
+    /// Creates a game record of `moves` played from `board`, with the Seven Tag Roster
+    /// set to unknown values and the `SetUp` and `FEN` tags for non-standard starts.
+    ///
+    /// Comments, NAGs and variations can then be added to the moves, and tags set
+    /// with [`PgnGame::set_tag`]. Fails if a move is illegal.
+    pub fn from_moves(board: &Board, moves: &[ChessMove]) -> Result<Self> {
+        let mut game = PgnGame::default();
+        for (tag, value) in
+            SEVEN_TAG_ROSTER
+                .iter()
+                .zip(["?", "?", "????.??.??", "?", "?", "?", "*"])
+        {
+            game.set_tag(tag, value);
+        }
+        if board.is_chess960() {
+            game.set_tag("Variant", "Chess960");
+        }
+        if board.to_string() != Board::default().to_string() {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &board.to_string());
+        }
+
+        let mut board = board.clone();
+        for &m in moves {
+            // the SAN of an illegal move can't be computed
+            let next = board.try_make_move(m)?;
+            game.moves.push(PgnMove::new(m, board.san(m)));
+            board = next;
+        }
+
+        Ok(game)
+    }
+
+    /// Sets the tag `name` to `value`, replacing its previous value if any.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+/// Builds the movetext tokens of a line, keeping track of the move numbers.
+struct MovetextWriter {
+    tokens: Vec<String>,
+    prefix: String,
+}
+
+impl MovetextWriter {
+    fn push(&mut self, token: String) {
+        self.tokens.push(format!("{}{}", self.prefix, token));
+        self.prefix.clear();
+    }
+
+    /// Pushes a comment one word at a time, so that long comments can be wrapped.
+    fn push_comment(&mut self, comment: &str) {
+        let comment = comment.replace('}', "");
+        let mut words: Vec<&str> = comment.split_whitespace().collect();
+        if words.is_empty() {
+            words.push("");
+        }
+        let last = words.len() - 1;
+        for (idx, word) in words.iter().enumerate() {
+            let open = if idx == 0 { "{" } else { "" };
+            let close = if idx == last { "}" } else { "" };
+            self.push(format!("{}{}{}", open, word, close));
+        }
+    }
+
+    fn push_line(&mut self, moves: &[PgnMove], mut fullmove: u16, mut side: Color) {
+        let mut need_number = true;
+        for m in moves {
+            for comment in &m.comments_before {
+                self.push_comment(comment);
+                need_number = true;
+            }
+
+            if side == Color::White {
+                self.push(format!("{}.", fullmove));
+            } else if need_number {
+                self.push(format!("{}...", fullmove));
+            }
+            self.push(m.san.clone());
+            need_number = false;
+
+            for nag in &m.nags {
+                self.push(format!("${}", nag));
+            }
+            for comment in &m.comments {
+                self.push_comment(comment);
+                need_number = true;
+            }
+            for variation in m.variations.iter().filter(|line| !line.is_empty()) {
+                self.prefix.push('(');
+                self.push_line(variation, fullmove, side);
+                self.tokens.last_mut().unwrap().push(')');
+                need_number = true;
+            }
+
+            if side == Color::Black {
+                fullmove += 1;
+            }
+            side = !side;
+        }
+    }
+}
+
+impl PgnGame {
+    /// Writes the game in PGN export format: the Seven Tag Roster, the other tags sorted
+    /// by name, and the movetext wrapped at 80 columns. Empty variations are left out.
+    ///
+    /// Fails if the `FEN` tag is not a valid position.
+    pub fn write_pgn(&self, f: &mut impl fmt::Write) -> Result<()> {
+        let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+        let result = result_to_str(self.result);
+
+        for tag in SEVEN_TAG_ROSTER {
+            let value = match tag {
+                "Result" => result,
+                "Date" => self.tag(tag).unwrap_or("????.??.??"),
+                _ => self.tag(tag).unwrap_or("?"),
+            };
+            writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+        }
+
+        let mut others: Vec<&(String, String)> = self
+            .tags
+            .iter()
+            .filter(|(tag, _)| !SEVEN_TAG_ROSTER.contains(&tag.as_str()))
+            .collect();
+        others.sort_by(|a, b| a.0.cmp(&b.0));
+        for (tag, value) in others {
+            writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+        }
+        writeln!(f)?;
+
+        let board = self.start_board()?;
+        let mut writer = MovetextWriter {
+            tokens: Vec::new(),
+            prefix: String::new(),
+        };
+        for comment in &self.comments {
+            writer.push_comment(comment);
+        }
+        writer.push_line(&self.moves, board.fullmove_number(), board.side_to_move());
+        writer.push(result.to_string());
+
+        let mut line = String::new();
+        for token in writer.tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        // games are separated by an empty line
+        writeln!(f, "{}", line)?;
+        writeln!(f)?;
+        Ok(())
+    }
+
+    /// Returns the game in PGN export format, see [`PgnGame::write_pgn`].
+    pub fn to_pgn(&self) -> Result<String> {
+        let mut pgn = String::new();
+        self.write_pgn(&mut pgn)?;
+        Ok(pgn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameResult;
+    use crate::pgn::PgnReader;
+    use crate::square::Square;
+    use std::str::FromStr;
+
+    fn moves(board: &Board, uci: &[&str]) -> Vec<ChessMove> {
+        let mut board = board.clone();
+        uci.iter()
+            .map(|uci| {
+                let m = board.parse_uci_move(uci).unwrap();
+                board = board.make_move(m);
+                m
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pgn_writer() {
+        let board = Board::default();
+        let mut game = PgnGame::from_moves(
+            &board,
+            &moves(&board, &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]),
+        )
+        .unwrap();
+        game.set_tag("White", "Tal, Mikhail");
+        game.set_tag("Event", "Club \"Open\"");
+        game.set_tag("Annotator", "Someone");
+        game.result = Some(GameResult::WhiteWins);
+        game.moves[0].comments_before.push("A classic".to_string());
+        game.moves[1].nags.push(1);
+        game.moves[1].comments.push("Solid".to_string());
+
+        let after_e4 = board.make_move(game.moves[0].chess_move);
+        let mut variation = PgnGame::from_moves(&after_e4, &moves(&after_e4, &["c7c5", "g1f3"]))
+            .unwrap()
+            .moves;
+        variation[0].comments.push("Sicilian".to_string());
+        game.moves[1].variations.push(variation);
+
+        let pgn = game.to_pgn().unwrap();
+        assert_eq!(
+            pgn,
+            r#"[Event "Club \"Open\""]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "Tal, Mikhail"]
+[Black "?"]
+[Result "1-0"]
+[Annotator "Someone"]
+
+{A classic} 1. e4 e5 $1 {Solid} (1... c5 {Sicilian} 2. Nf3) 2. Nf3 Nc6 3. Bb5
+1-0
+
+"#
+        );
+
+        let parsed = PgnGame::from_str(&pgn).unwrap();
+        assert_eq!(parsed.moves, game.moves);
+        assert_eq!(parsed.result, game.result);
+        assert_eq!(parsed.to_pgn().unwrap(), pgn);
+
+        let games = format!("{}{}", pgn, pgn);
+        assert_eq!(PgnReader::new(games.as_bytes()).count(), 2);
+
+        game.moves[2].variations.push(Vec::new());
+        assert_eq!(game.to_pgn().unwrap(), pgn);
+        game.set_tag("FEN", "junk");
+        assert!(game.to_pgn().is_err());
+    }
+
+    #[test]
+    fn test_pgn_writer_setup_and_wrapping() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 b Q - 3 40").unwrap();
+        let shuffle = ["e8d8", "a1a2", "d8e8", "a2a1"];
+        let uci: Vec<&str> = shuffle.iter().cycle().take(40).copied().collect();
+        let game = PgnGame::from_moves(&board, &moves(&board, &uci)).unwrap();
+        assert_eq!(game.tag("SetUp"), Some("1"));
+        assert_eq!(game.tag("FEN"), Some("4k3/8/8/8/8/8/8/R3K3 b Q - 3 40"));
+
+        let pgn = game.to_pgn().unwrap();
+        let movetext: Vec<&str> = pgn
+            .lines()
+            .skip_while(|line| !line.is_empty())
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .collect();
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(movetext[0].starts_with("40... Kd8 41. Ra2 Ke8 42. Ra1"));
+        assert!(movetext.last().unwrap().ends_with('*'));
+
+        let parsed = PgnGame::from_str(&pgn).unwrap();
+        assert_eq!(parsed.moves, game.moves);
+        assert_eq!(parsed.tag("FEN"), game.tag("FEN"));
+
+        assert!(PgnGame::from_moves(&Board::default(), &moves(&board, &["e8d8"])).is_err());
+        let empty_square = ChessMove::new(
+            Square::from_str("e4").unwrap(),
+            Square::from_str("e5").unwrap(),
+            None,
+        );
+        assert!(PgnGame::from_moves(&Board::default(), &[empty_square]).is_err());
+    }
+}
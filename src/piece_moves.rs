@@ -1,6 +1,7 @@
 use crate::bitboard::BitBoard;
 use crate::board::Board;
 use crate::color::Color;
+use crate::file::File;
 use crate::pieces::Piece;
 use crate::square::Square;
 
@@ -236,35 +237,102 @@ impl KingMoves {
                 & board.get_color_bitboard(board.side_to_move()))
             | BitBoard::from_square(dest);
 
+        !KingMoves::attacked(board, dest, combined)
+    }
+
+    /// Checks whether `square` is attacked by the side not to move, with `combined`
+    /// as the blockers of the sliding pieces.
+    #[inline(always)]
+    pub fn attacked(board: &Board, square: Square, combined: BitBoard) -> bool {
         let mut attackers = BitBoard(0);
 
         let enemy_rooks = (board.get_piece_bitboard(Piece::Rook)
             | board.get_piece_bitboard(Piece::Queen))
             & board.get_color_bitboard(!board.side_to_move());
-        attackers |= magic::get_rook_moves(dest, combined) & enemy_rooks;
+        attackers |= magic::get_rook_moves(square, combined) & enemy_rooks;
 
         let enemy_bishops = (board.get_piece_bitboard(Piece::Bishop)
             | board.get_piece_bitboard(Piece::Queen))
             & board.get_color_bitboard(!board.side_to_move());
-        attackers |= magic::get_bishop_moves(dest, combined) & enemy_bishops;
+        attackers |= magic::get_bishop_moves(square, combined) & enemy_bishops;
 
-        let knight_rays = magic::get_knight_moves(dest);
+        let knight_rays = magic::get_knight_moves(square);
         attackers |= knight_rays
             & board.get_piece_bitboard(Piece::Knight)
             & board.get_color_bitboard(!board.side_to_move());
 
-        let king_rays = magic::get_king_moves(dest);
+        let king_rays = magic::get_king_moves(square);
         attackers |= king_rays
             & board.get_piece_bitboard(Piece::King)
             & board.get_color_bitboard(!board.side_to_move());
 
         attackers |= magic::get_pawn_attacks(
-            dest,
+            square,
             board.side_to_move(),
             board.get_piece_bitboard(Piece::Pawn) & board.get_color_bitboard(!board.side_to_move()),
         );
 
-        attackers.is_empty()
+        !attackers.is_empty()
+    }
+
+    /// Returns the destination squares of the legal castling moves of the side to move,
+    /// which must not be in check.
+    ///
+    /// Castling is encoded as the king moving two squares, or as the king capturing its
+    /// own rook on Chess960 boards.
+    #[inline(always)]
+    pub fn legal_castles(board: &Board) -> BitBoard {
+        let rights = board.castle_rights();
+        let color = board.side_to_move();
+        let mut moves = BitBoard(0);
+
+        if rights.has_kingside(color) {
+            moves |= KingMoves::legal_castle(
+                board,
+                rights.kingside_rook(color),
+                rights.kingside_squares(color),
+                File::G,
+            );
+        }
+
+        if rights.has_queenside(color) {
+            moves |= KingMoves::legal_castle(
+                board,
+                rights.queenside_rook(color),
+                rights.queenside_squares(color),
+                File::C,
+            );
+        }
+
+        moves
+    }
+
+    #[inline(always)]
+    fn legal_castle(board: &Board, rook: Square, empty: BitBoard, king_dest: File) -> BitBoard {
+        let combined = board.get_combined_bitboard();
+        if !(combined & empty).is_empty() {
+            return BitBoard(0);
+        }
+
+        let color = board.side_to_move();
+        let king_square = board.get_king_square(color);
+        let king_dest = Square::new(color.starting_rank(), king_dest);
+
+        // the castling rook may be shielding the king's path, so it doesn't block attacks
+        let combined = combined ^ BitBoard::from_square(king_square) ^ BitBoard::from_square(rook);
+        let path = magic::get_between(king_square, king_dest) | BitBoard::from_square(king_dest);
+        if path
+            .get_squares()
+            .any(|square| KingMoves::attacked(board, square, combined))
+        {
+            return BitBoard(0);
+        }
+
+        if board.is_chess960() {
+            BitBoard::from_square(rook)
+        } else {
+            BitBoard::from_square(king_dest)
+        }
     }
 }
 impl PieceMoves for KingMoves {
@@ -287,25 +355,7 @@ impl PieceMoves for KingMoves {
         }
 
         if !T::IN_CHECK {
-            if board.castle_rights().has_kingside(color)
-                && (combined & board.castle_rights().kingside_squares(color)).is_empty()
-            {
-                let first = king_square.right().unwrap();
-                let second = first.right().unwrap();
-                if KingMoves::legal_move(board, first) && KingMoves::legal_move(board, second) {
-                    moves ^= BitBoard::from_square(second);
-                }
-            }
-
-            if board.castle_rights().has_queenside(color)
-                && (combined & board.castle_rights().queenside_squares(color)).is_empty()
-            {
-                let first = king_square.left().unwrap();
-                let second = first.left().unwrap();
-                if KingMoves::legal_move(board, first) && KingMoves::legal_move(board, second) {
-                    moves ^= BitBoard::from_square(second);
-                }
-            }
+            moves ^= KingMoves::legal_castles(board);
         }
 
         if !moves.is_empty() {
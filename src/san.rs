@@ -0,0 +1,258 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::movegen::MoveGen;
+use crate::pieces::Piece;
+use crate::square::Square;
+
+use anyhow::{Error, anyhow, bail};
+use std::str::FromStr;
+
+impl Board {
+    /// Formats the legal move `m` in Standard Algebraic Notation (e.g., "Nbd7", "exd6",
+    /// "O-O-O" or "e8=Q+"), disambiguating by file, then rank, then full square.
+    pub fn san(&self, m: ChessMove) -> String {
+        let piece = self.get_piece(m.source).unwrap();
+        let mut san = String::new();
+
+        if piece == Piece::King && self.is_castle(m) {
+            if m.dest.get_file().to_index() > m.source.get_file().to_index() {
+                san.push_str("O-O");
+            } else {
+                san.push_str("O-O-O");
+            }
+        } else {
+            let capture = self.get_piece(m.dest).is_some()
+                || (piece == Piece::Pawn && Some(m.dest) == self.en_passant());
+
+            if piece == Piece::Pawn {
+                if capture {
+                    san.push_str(&m.source.to_string()[..1]);
+                }
+            } else {
+                san.push_str(&piece.to_string(Color::White));
+
+                let others: Vec<Square> = MoveGen::new_legal(self)
+                    .filter(|other| {
+                        other.dest == m.dest
+                            && other.source != m.source
+                            && self.get_piece(other.source) == Some(piece)
+                    })
+                    .map(|other| other.source)
+                    .collect();
+                let source = m.source.to_string();
+                if !others.is_empty() {
+                    if others.iter().all(|sq| sq.get_file() != m.source.get_file()) {
+                        san.push_str(&source[..1]);
+                    } else if others.iter().all(|sq| sq.get_rank() != m.source.get_rank()) {
+                        san.push_str(&source[1..]);
+                    } else {
+                        san.push_str(&source);
+                    }
+                }
+            }
+
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&m.dest.to_string());
+
+            if let Some(promotion) = m.promotion {
+                san.push('=');
+                san.push_str(&promotion.to_string(Color::White));
+            }
+        }
+
+        let board = self.make_move(m);
+        if !board.get_checkers_bitboard().is_empty() {
+            if MoveGen::new_legal(&board).len() == 0 {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+
+        san
+    }
+
+    /// Parses a move in Standard Algebraic Notation and checks that it is legal.
+    ///
+    /// Common sloppy forms are accepted as well: a missing or extra `x`, `0-0` for
+    /// castling, a lowercase promotion piece with or without `=`, and any check,
+    /// mate or annotation suffix.
+    pub fn parse_san(&self, san: &str) -> Result<ChessMove, Error> {
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        let castle = match text {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None,
+        };
+        if let Some(kingside) = castle {
+            let king = self.get_king_square(self.side_to_move());
+            return MoveGen::new_legal(self)
+                .find(|m| {
+                    m.source == king
+                        && self.is_castle(*m)
+                        && (m.dest.get_file().to_index() > king.get_file().to_index()) == kingside
+                })
+                .ok_or_else(|| anyhow!("illegal move {} in position {}", san, self));
+        }
+
+        let mut chars: Vec<char> = text.chars().filter(|c| !matches!(c, 'x' | '-')).collect();
+
+        let piece = match chars.first() {
+            Some('N') => Some(Piece::Knight),
+            Some('B') => Some(Piece::Bishop),
+            Some('R') => Some(Piece::Rook),
+            Some('Q') => Some(Piece::Queen),
+            Some('K') => Some(Piece::King),
+            _ => None,
+        };
+        if piece.is_some() {
+            chars.remove(0);
+        }
+        let piece = piece.unwrap_or(Piece::Pawn);
+
+        let mut promotion = None;
+        if piece == Piece::Pawn && chars.len() > 2 {
+            promotion = match chars.last().map(|c| c.to_ascii_uppercase()) {
+                Some('N') => Some(Piece::Knight),
+                Some('B') => Some(Piece::Bishop),
+                Some('R') => Some(Piece::Rook),
+                Some('Q') => Some(Piece::Queen),
+                _ => None,
+            };
+            if promotion.is_some() {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+
+        if chars.len() < 2 || chars.len() > 4 {
+            bail!("invalid move '{}'", san);
+        }
+        let dest: String = chars[chars.len() - 2..].iter().collect();
+        let dest = Square::from_str(&dest).map_err(|_| anyhow!("invalid move '{}'", san))?;
+
+        let mut source_file = None;
+        let mut source_rank = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' if source_file.is_none() && source_rank.is_none() => {
+                    source_file = Some(*c as usize - 'a' as usize)
+                }
+                '1'..='8' if source_rank.is_none() => {
+                    source_rank = Some(*c as usize - '1' as usize)
+                }
+                _ => bail!("invalid move '{}'", san),
+            }
+        }
+
+        let mut candidates = MoveGen::new_legal(self).filter(|m| {
+            m.dest == dest
+                && m.promotion == promotion
+                && self.get_piece(m.source) == Some(piece)
+                && !(piece == Piece::King && self.is_castle(*m))
+                && source_file.is_none_or(|file| m.source.get_file().to_index() == file)
+                && source_rank.is_none_or(|rank| m.source.get_rank().to_index() == rank)
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(m), None) => Ok(m),
+            (Some(_), Some(_)) => bail!("ambiguous move {} in position {}", san, self),
+            (None, _) => bail!("illegal move {} in position {}", san, self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_san_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            KIWIPETE,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+            let sans: Vec<String> = moves.iter().map(|m| board.san(*m)).collect();
+            for (m, san) in moves.iter().zip(&sans) {
+                assert_eq!(board.parse_san(san).unwrap(), *m, "{} in {}", san, fen);
+                assert_eq!(sans.iter().filter(|other| *other == san).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_san_format() {
+        let board = Board::from_str(KIWIPETE).unwrap();
+        let san = |uci: &str| board.san(board.parse_uci_move(uci).unwrap());
+        assert_eq!(san("e1g1"), "O-O");
+        assert_eq!(san("e1c1"), "O-O-O");
+        assert_eq!(san("e5d7"), "Nxd7");
+        assert_eq!(san("d5e6"), "dxe6");
+        assert_eq!(san("c3b1"), "Nb1");
+        assert_eq!(san("f3f6"), "Qxf6");
+        assert_eq!(san("e5g6"), "Nxg6");
+
+        // file, rank and full square disambiguation
+        let board = Board::from_str("1k6/8/1q6/1N6/8/1N3N2/8/4K3 w - - 0 1").unwrap();
+        let san = |uci: &str| board.san(board.parse_uci_move(uci).unwrap());
+        assert_eq!(san("b3d4"), "Nb3d4");
+        assert_eq!(san("f3d4"), "Nfd4");
+        assert_eq!(san("b5d4"), "N5d4");
+        assert_eq!(san("b5c7"), "Nc7");
+
+        let board = Board::from_str("8/4P1k1/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(board.san(board.parse_uci_move("e7e8q").unwrap()), "e8=Q");
+        assert_eq!(board.san(board.parse_uci_move("e7e8n").unwrap()), "e8=N+");
+
+        let board =
+            Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2")
+                .unwrap();
+        assert_eq!(board.san(board.parse_uci_move("d8h4").unwrap()), "Qh4#");
+    }
+
+    #[test]
+    fn test_san_parse_sloppy() {
+        let board = Board::from_str(KIWIPETE).unwrap();
+        let uci = |san: &str| board.parse_san(san).unwrap().to_string();
+        assert_eq!(uci("0-0"), "e1g1");
+        assert_eq!(uci("O-O-O+"), "e1c1");
+        assert_eq!(uci("Nd7"), "e5d7");
+        assert_eq!(uci("Nxd7!?"), "e5d7");
+        assert_eq!(uci("de6"), "d5e6");
+        assert_eq!(uci("Qxh3"), "f3h3");
+        assert_eq!(uci("Ne5xg6"), "e5g6");
+
+        assert!(board.parse_san("Nd2").is_err());
+        assert!(board.parse_san("Kf2").is_err());
+        assert!(board.parse_san("e9").is_err());
+        assert!(board.parse_san("").is_err());
+
+        let board = Board::from_str("1k6/8/1q6/1N6/8/1N3N2/8/4K3 w - - 0 1").unwrap();
+        assert!(
+            board
+                .parse_san("Nd4")
+                .unwrap_err()
+                .to_string()
+                .contains("ambiguous")
+        );
+
+        let board = Board::from_str("8/4P1k1/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        for san in ["e8=Q", "e8Q", "e8q", "e8=q"] {
+            assert_eq!(board.parse_san(san).unwrap().to_string(), "e7e8q");
+        }
+        assert!(board.parse_san("e8").is_err());
+    }
+}
@@ -0,0 +1,537 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::movegen::MoveGen;
+use crate::pieces::{ALL_PIECES, Piece};
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Score of being checkmated at the root; a mate found `n` plies deep scores `MATE - n`.
+pub const MATE: i32 = 30_000;
+/// Deepest ply the search reaches, quiescence included.
+pub const MAX_PLY: usize = 64;
+const INFINITY: i32 = 32_000;
+
+/// The limits of a search, as given by `go` in UCI or the clocks in XBoard.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Duration,
+    pub binc: Duration,
+    pub movestogo: Option<u32>,
+    /// Search until stopped from outside.
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    /// Returns how long `side` should think about its move, or `None` for no time limit.
+    ///
+    /// With a clock, a share of the remaining time is used, assuming 30 more moves when
+    /// `movestogo` is not given, plus most of the increment.
+    pub fn time_budget(&self, side: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime);
+        }
+
+        let (time, inc) = match side {
+            Color::White => (self.wtime?, self.winc),
+            Color::Black => (self.btime?, self.binc),
+        };
+        let moves = self.movestogo.unwrap_or(30).max(1);
+        let budget = time / moves + inc * 3 / 4;
+        // keep a margin for the communication with the GUI
+        Some(budget.min(time.saturating_sub(Duration::from_millis(50))))
+    }
+}
+
+/// A search score from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    /// An evaluation in centipawns.
+    Centipawns(i32),
+    /// A forced mate in that many moves, negative when the side to move gets mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Converts a score returned by the search, telling mates apart.
+    pub fn from_value(value: i32) -> Self {
+        let plies = MATE - value.abs();
+        if plies > MAX_PLY as i32 {
+            Score::Centipawns(value)
+        } else if value > 0 {
+            Score::Mate((plies + 1) / 2)
+        } else {
+            Score::Mate(-(plies + 1) / 2)
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    /// Formats the score as in UCI `info` lines, like `cp 35` or `mate -2`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
+    }
+}
+
+/// What a search reports after each iteration of iterative deepening.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub time: Duration,
+    /// The principal variation, starting with the best move.
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchInfo {
+    /// Returns the nodes searched per second.
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(1e-3)) as u64
+    }
+}
+
+#[inline(always)]
+fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight => 320,
+        Piece::Bishop => 330,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+/// Evaluates the position in centipawns for the side to move: material, plus small
+/// bonuses for advanced pawns and centralized minor pieces and queens.
+pub fn evaluate(board: &Board) -> i32 {
+    let mut score = 0;
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White { 1 } else { -1 };
+        for piece in ALL_PIECES {
+            let pieces = board.get_piece_bitboard(piece) & board.get_color_bitboard(color);
+            for square in pieces.get_squares() {
+                let file = square.get_file().to_index() as i32;
+                let rank = match color {
+                    Color::White => square.get_rank().to_index() as i32,
+                    Color::Black => 7 - square.get_rank().to_index() as i32,
+                };
+                // 0 on the edges up to 6 on the four center squares
+                let center = 6 - (2 * file - 7).abs() / 2 - (2 * rank - 7).abs() / 2;
+                let bonus = match piece {
+                    Piece::Pawn => 5 * (rank - 1) + if (2..=5).contains(&file) { 5 } else { 0 },
+                    Piece::Knight => 5 * center - 15,
+                    Piece::Bishop => 3 * center - 5,
+                    Piece::Queen => center,
+                    Piece::Rook | Piece::King => 0,
+                };
+                score += sign * (piece_value(piece) + bonus);
+            }
+        }
+    }
+
+    match board.side_to_move() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+/// A negamax alpha-beta search with principal variation search, iterative deepening
+/// and a captures-only quiescence search at the leaves.
+///
+/// The search stops at the depth, node and time limits, or as soon as the stop flag
+/// is set from another thread, and then returns the best move of the last iteration
+/// it completed.
+pub struct Search {
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    /// Hashes of the positions of the game before the root, to detect repetitions.
+    history: Vec<u64>,
+    /// Hashes of the positions from the root down to the current node.
+    path: Vec<u64>,
+    /// The principal variation of the previous iteration, searched first.
+    pv: Vec<ChessMove>,
+    nodes: u64,
+    start: Instant,
+    deadline: Option<Instant>,
+    completed_depth: usize,
+    aborted: bool,
+}
+
+impl Search {
+    pub fn new(limits: SearchLimits, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            limits,
+            stop,
+            history: Vec::new(),
+            path: Vec::with_capacity(MAX_PLY),
+            pv: Vec::new(),
+            nodes: 0,
+            start: Instant::now(),
+            deadline: None,
+            completed_depth: 0,
+            aborted: false,
+        }
+    }
+
+    /// Sets the hashes of the positions played before the root, oldest first, so that
+    /// repeating one of them is scored as a draw.
+    pub fn with_history(mut self, history: Vec<u64>) -> Self {
+        self.history = history;
+        self
+    }
+
+    #[inline(always)]
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Searches `board` with increasing depths, calling `report` after each completed
+    /// iteration. Returns `None` only if there are no legal moves.
+    pub fn run(&mut self, board: &Board, mut report: impl FnMut(&SearchInfo)) -> Option<ChessMove> {
+        let mut best = MoveGen::new_legal(board).next()?;
+
+        self.start = Instant::now();
+        self.deadline = self
+            .limits
+            .time_budget(board.side_to_move())
+            .map(|budget| self.start + budget);
+        self.nodes = 0;
+        self.completed_depth = 0;
+        self.aborted = false;
+        self.pv.clear();
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_PLY).clamp(1, MAX_PLY - 1);
+        for depth in 1..=max_depth {
+            let mut pv = Vec::new();
+            self.path.clear();
+            let score = self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+            if self.aborted {
+                break;
+            }
+
+            self.completed_depth = depth;
+            if let Some(&m) = pv.first() {
+                best = m;
+            }
+            self.pv = pv.clone();
+            let time = self.start.elapsed();
+            report(&SearchInfo {
+                depth,
+                score: Score::from_value(score),
+                nodes: self.nodes,
+                time,
+                pv,
+            });
+
+            // no shorter mate can be found by searching deeper
+            if MATE - score.abs() <= depth as i32 {
+                break;
+            }
+            // the next iteration would most likely not finish in time
+            if let Some(deadline) = self.deadline
+                && time * 2 > deadline - self.start
+            {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+
+    /// Checks the limits every few nodes, setting `aborted` once one is reached.
+    /// The first iteration is always completed unless the search is stopped.
+    #[inline(always)]
+    fn should_abort(&mut self) -> bool {
+        if !self.aborted && self.nodes.is_multiple_of(1024) {
+            let over_limits = self.completed_depth > 0
+                && (self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+                    || self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes));
+            self.aborted = over_limits || self.stop.load(Ordering::Relaxed);
+        }
+        self.aborted
+    }
+
+    /// Returns true for draws by the fifty-move rule, insufficient material, or
+    /// repetition of a position of the game or of the search path.
+    fn is_draw(&self, board: &Board) -> bool {
+        if board.halfmove_clock() >= 100 || board.is_insufficient_material() {
+            return true;
+        }
+        let hash = board.get_hash();
+        // positions before the last capture or pawn move can't come back
+        self.path
+            .iter()
+            .rev()
+            .chain(self.history.iter().rev())
+            .take(board.halfmove_clock() as usize)
+            .any(|&previous| previous == hash)
+    }
+
+    /// Returns the legal moves of `board`, most promising first: the move of the previous
+    /// principal variation, then captures by most valuable victim and least valuable
+    /// attacker, then promotions.
+    fn ordered_moves(&self, board: &Board, ply: usize, noisy_only: bool) -> Vec<ChessMove> {
+        let side = board.side_to_move();
+        let pv_move = self.pv.get(ply).copied();
+        let mut moves: Vec<(ChessMove, i32)> = MoveGen::new_legal(board)
+            .filter_map(|m| {
+                let attacker = board.get_piece(m.source).unwrap();
+                let victim = match board.get_piece_and_color(m.dest) {
+                    // castling in Chess960 takes an own rook
+                    Some((piece, color)) if color != side => Some(piece),
+                    None if attacker == Piece::Pawn && board.en_passant() == Some(m.dest) => {
+                        Some(Piece::Pawn)
+                    }
+                    _ => None,
+                };
+                let promotion = m.promotion.map_or(0, piece_value);
+                if noisy_only && victim.is_none() && promotion == 0 {
+                    return None;
+                }
+
+                let score = if Some(m) == pv_move {
+                    INFINITY
+                } else {
+                    victim.map_or(0, |victim| {
+                        10 * piece_value(victim) - piece_value(attacker) / 10 + 1
+                    }) + promotion
+                };
+                Some((m, score))
+            })
+            .collect();
+        moves.sort_by_key(|&(_, score)| Reverse(score));
+        moves.into_iter().map(|(m, _)| m).collect()
+    }
+
+    fn negamax(
+        &mut self,
+        board: &Board,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<ChessMove>,
+    ) -> i32 {
+        pv.clear();
+        self.nodes += 1;
+        if self.should_abort() {
+            return 0;
+        }
+        if ply > 0 && self.is_draw(board) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let moves = self.ordered_moves(board, ply, false);
+        if moves.is_empty() {
+            return if board.get_checkers_bitboard().is_empty() {
+                0
+            } else {
+                -MATE + ply as i32
+            };
+        }
+
+        self.path.push(board.get_hash());
+        let mut child_pv = Vec::new();
+        let mut best = -INFINITY;
+        for (idx, &m) in moves.iter().enumerate() {
+            let child = board.make_move(m);
+            let score = if idx == 0 {
+                -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv)
+            } else {
+                // prove that the move is worse than the best one with a null window,
+                // and search it again with the full window if it isn't
+                let score = -self.negamax(
+                    &child,
+                    depth - 1,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_pv,
+                );
+                if score > alpha && score < beta {
+                    -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv)
+                } else {
+                    score
+                }
+            };
+            if self.aborted {
+                break;
+            }
+
+            best = best.max(score);
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        self.path.pop();
+        best
+    }
+
+    /// Searches only captures and promotions, or every move when in check, until the
+    /// position is quiet, so that the evaluation is not taken in the middle of an exchange.
+    fn quiescence(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_abort() {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return evaluate(board);
+        }
+
+        let in_check = !board.get_checkers_bitboard().is_empty();
+        let mut best = if in_check {
+            -MATE + ply as i32
+        } else {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            stand_pat
+        };
+
+        for m in self.ordered_moves(board, ply, !in_check) {
+            let score = -self.quiescence(&board.make_move(m), ply + 1, -beta, -alpha);
+            if self.aborted {
+                return 0;
+            }
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn run_search(fen: &str, limits: SearchLimits) -> (Option<ChessMove>, Vec<SearchInfo>) {
+        let board = Board::from_str(fen).unwrap();
+        let mut infos = Vec::new();
+        let best = Search::new(limits, Arc::new(AtomicBool::new(false)))
+            .run(&board, |info| infos.push(info.clone()));
+        (best, infos)
+    }
+
+    fn depth(depth: usize) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    #[test]
+    fn test_search_mates() {
+        let (best, infos) = run_search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", depth(4));
+        assert_eq!(best.unwrap().to_string(), "a1a8");
+        assert_eq!(infos.last().unwrap().score, Score::Mate(1));
+        // the search stops once the mate is proven
+        assert_eq!(infos.len(), 1);
+
+        let (best, infos) = run_search("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", depth(5));
+        let info = infos.last().unwrap();
+        assert_eq!(info.score, Score::Mate(2));
+        assert_eq!(info.pv.len(), 3);
+        assert_eq!(best, info.pv.first().copied());
+
+        let (best, infos) = run_search("6k1/8/8/8/8/8/r5PP/7K b - - 0 1", depth(3));
+        assert_eq!(best.unwrap().to_string(), "a2a1");
+        assert_eq!(infos.last().unwrap().score, Score::Mate(1));
+        let (best, infos) = run_search("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", depth(3));
+        assert_eq!(best, None);
+        assert!(infos.is_empty());
+
+        assert_eq!(Score::from_value(MATE - 3), Score::Mate(2));
+        assert_eq!(Score::from_value(-MATE + 2), Score::Mate(-1));
+        assert_eq!(Score::from_value(150).to_string(), "cp 150");
+    }
+
+    #[test]
+    fn test_search_material_and_draws() {
+        let (best, infos) = run_search("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", depth(3));
+        assert_eq!(best.unwrap().to_string(), "d1d5");
+        assert!(matches!(infos.last().unwrap().score, Score::Centipawns(cp) if cp > 300));
+
+        let (_, infos) = run_search("4k3/8/8/8/8/8/8/4K3 w - - 0 1", depth(4));
+        assert_eq!(infos.last().unwrap().score, Score::Centipawns(0));
+
+        // a queen down, going back to a position of the game is the best option
+        let board = Board::from_str("q3k3/8/8/8/8/8/8/4K3 w - - 5 10").unwrap();
+        let previous = Board::from_str("q3k3/8/8/8/8/8/5K2/8 b - - 4 9").unwrap();
+        let mut infos = Vec::new();
+        Search::new(depth(3), Arc::new(AtomicBool::new(false)))
+            .with_history(vec![previous.get_hash()])
+            .run(&board, |info| infos.push(info.clone()));
+        let info = infos.last().unwrap();
+        assert_eq!(info.pv[0].to_string(), "e1f2");
+        assert_eq!(info.score, Score::Centipawns(0));
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let (best, infos) = run_search(fen, depth(3));
+        assert!(best.is_some());
+        assert_eq!(
+            infos.iter().map(|info| info.depth).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert!(infos.windows(2).all(|w| w[0].nodes < w[1].nodes));
+
+        let limits = SearchLimits {
+            nodes: Some(5000),
+            ..SearchLimits::default()
+        };
+        let board = Board::from_str(fen).unwrap();
+        let mut search = Search::new(limits, Arc::new(AtomicBool::new(false)));
+        search.run(&board, |_| {});
+        assert!(search.nodes() < 5000 + 1024);
+
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_millis(50)),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let (best, _) = run_search(fen, limits);
+        assert!(best.is_some());
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // a search stopped before it starts still returns a legal move
+        let stop = Arc::new(AtomicBool::new(true));
+        let best = Search::new(SearchLimits::default(), stop).run(&board, |_| {});
+        assert!(board.is_legal(best.unwrap()));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::color::Color;
+use crate::fen::FenError;
 use crate::file::File;
 use crate::rank::Rank;
-use anyhow::{Error, bail};
 use std::fmt;
 use std::str::FromStr;
 
@@ -114,29 +114,18 @@ impl Default for Square {
 }
 
 impl FromStr for Square {
-    type Err = Error;
+    type Err = FenError;
 
     /// Parses a square from a string representation (e.g., "a1").
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 2 {
-            bail!("error");
+        // matching on the bytes also rejects multi-byte characters
+        match *s.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Square::new(
+                Rank::from_index((rank - b'1') as usize),
+                File::from_index((file - b'a') as usize),
+            )),
+            _ => Err(FenError::InvalidSquare(s.to_string())),
         }
-
-        let mut chars = s.chars();
-        let file_char = chars.next().unwrap();
-        let rank_char = chars.next().unwrap();
-
-        let file = match file_char {
-            'a'..='h' => File::from_index(file_char as usize - 'a' as usize),
-            _ => bail!("error"),
-        };
-
-        let rank = match rank_char.to_digit(10) {
-            Some(n @ 1..=8) => Rank::from_index((n - 1) as usize),
-            _ => bail!("error"),
-        };
-
-        Ok(Square::new(rank, file))
     }
 }
 
@@ -187,6 +176,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_square_from_str_error() {
+        assert_eq!(
+            Square::from_str("i1"),
+            Err(FenError::InvalidSquare("i1".to_string()))
+        );
+        assert!(Square::from_str("a9").is_err());
+        assert!(Square::from_str("a10").is_err());
+        assert!(Square::from_str("").is_err());
+        assert!(Square::from_str("é").is_err());
+        assert!(Square::from_str("aé").is_err());
+    }
+
     #[test]
     fn test_rank_fmt() {
         assert_eq!(format!("{}", Square::new(Rank::First, File::A)), "a1");
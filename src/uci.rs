@@ -0,0 +1,353 @@
+use crate::board::Board;
+use crate::search::{Search, SearchLimits};
+
+use anyhow::{Context, Result, bail};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Where the engine writes its replies, shared with the search thread.
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// Writes a line to `output`, ignoring errors since there is nobody left to tell.
+pub fn send(output: &Output, line: &str) {
+    let mut output = output.lock().unwrap();
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+impl SearchLimits {
+    /// Parses the arguments of `go`, ignoring the ones it doesn't know.
+    pub fn parse(args: &[&str]) -> Result<Self> {
+        let mut limits = SearchLimits::default();
+        let mut tokens = args.iter();
+        while let Some(&token) = tokens.next() {
+            let mut value = || -> Result<u64> {
+                let value = tokens
+                    .next()
+                    .with_context(|| format!("{} expects a value", token))?;
+                // clocks can go negative when the engine is late
+                Ok(value
+                    .parse::<i64>()
+                    .with_context(|| format!("invalid {} '{}'", token, value))?
+                    .max(0) as u64)
+            };
+            match token {
+                "depth" => limits.depth = Some(value()? as usize),
+                "nodes" => limits.nodes = Some(value()?),
+                "movetime" => limits.movetime = Some(Duration::from_millis(value()?)),
+                "wtime" => limits.wtime = Some(Duration::from_millis(value()?)),
+                "btime" => limits.btime = Some(Duration::from_millis(value()?)),
+                "winc" => limits.winc = Duration::from_millis(value()?),
+                "binc" => limits.binc = Duration::from_millis(value()?),
+                "movestogo" => limits.movestogo = Some(value()? as u32),
+                "infinite" => limits.infinite = true,
+                _ => {}
+            }
+        }
+        Ok(limits)
+    }
+}
+
+/// Reads the arguments of `position`: `startpos` or `fen <fen>`, then optionally
+/// `moves` followed by moves in UCI notation.
+///
+/// Returns the position along with the hashes of the positions before it, oldest first.
+pub fn parse_position(args: &[&str]) -> Result<(Board, Vec<u64>)> {
+    let moves_idx = args.iter().position(|&arg| arg == "moves");
+    let (setup, moves) = match moves_idx {
+        Some(idx) => (&args[..idx], &args[idx + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut board = match setup {
+        ["startpos"] => Board::default(),
+        ["fen", fen @ ..] if !fen.is_empty() => Board::from_str(&fen.join(" "))?,
+        _ => bail!("invalid position '{}'", args.join(" ")),
+    };
+    let mut history = Vec::with_capacity(moves.len());
+    for uci in moves {
+        let m = board.parse_uci_move(uci)?;
+        history.push(board.get_hash());
+        board = board.make_move(m);
+    }
+    Ok((board, history))
+}
+
+/// Runs a search of `board` and sends its `info` and `bestmove` lines.
+fn search(
+    board: Board,
+    history: Vec<u64>,
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    output: Output,
+) {
+    let infinite = limits.infinite;
+    let best = Search::new(limits, stop.clone())
+        .with_history(history)
+        .run(&board, |info| {
+            let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+            send(
+                &output,
+                &format!(
+                    "info depth {} score {} nodes {} nps {} time {} pv {}",
+                    info.depth,
+                    info.score,
+                    info.nodes,
+                    info.nps(),
+                    info.time.as_millis(),
+                    pv.join(" ")
+                ),
+            );
+        });
+
+    // the best move of an infinite search is only sent after `stop`
+    while infinite && !stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    match best {
+        Some(m) => send(&output, &format!("bestmove {}", m)),
+        None => send(&output, "bestmove 0000"),
+    }
+}
+
+/// The state of a UCI session: the current position, the options and the running search.
+pub struct Uci {
+    board: Board,
+    /// Hashes of the positions played before `board`, to detect repetitions.
+    history: Vec<u64>,
+    chess960: bool,
+    output: Output,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+}
+
+impl Uci {
+    pub fn new(output: Output) -> Self {
+        Self {
+            board: Board::default(),
+            history: Vec::new(),
+            chess960: false,
+            output,
+            stop: Arc::new(AtomicBool::new(false)),
+            search: None,
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or the end of the input.
+    pub fn run(&mut self, input: impl BufRead) -> Result<()> {
+        for line in input.lines() {
+            if !self.handle(&line?) {
+                break;
+            }
+        }
+        self.stop_search();
+        Ok(())
+    }
+
+    /// Handles a single command, returning false on `quit`.
+    ///
+    /// Unknown commands and invalid arguments are reported with `info string` and
+    /// otherwise ignored, as the protocol asks.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        let result = match command {
+            "uci" => {
+                send(&self.output, "id name luna-chess");
+                send(&self.output, "id author luna-chess developers");
+                send(
+                    &self.output,
+                    "option name UCI_Chess960 type check default false",
+                );
+                send(&self.output, "uciok");
+                Ok(())
+            }
+            "isready" => {
+                send(&self.output, "readyok");
+                Ok(())
+            }
+            "ucinewgame" => {
+                self.stop_search();
+                self.board = Board::default();
+                self.history.clear();
+                Ok(())
+            }
+            "setoption" => self.set_option(args),
+            "position" => {
+                self.stop_search();
+                parse_position(args).map(|(board, history)| {
+                    self.board = board;
+                    self.history = history;
+                })
+            }
+            "go" => SearchLimits::parse(args).map(|limits| self.go(limits)),
+            "stop" => {
+                self.stop_search();
+                Ok(())
+            }
+            "quit" => return false,
+            _ => Err(anyhow::anyhow!("unknown command '{}'", line.trim())),
+        };
+
+        if let Err(err) = result {
+            send(&self.output, &format!("info string {:#}", err));
+        }
+        true
+    }
+
+    /// Handles `setoption name <name> [value <value>]`.
+    fn set_option(&mut self, args: &[&str]) -> Result<()> {
+        let value_idx = args.iter().position(|&arg| arg == "value");
+        let name = args[..value_idx.unwrap_or(args.len())]
+            .iter()
+            .skip_while(|&&arg| arg == "name")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value = value_idx.map(|idx| args[idx + 1..].join(" "));
+
+        match (name.to_lowercase().as_str(), value.as_deref()) {
+            ("uci_chess960", Some("true")) => self.chess960 = true,
+            ("uci_chess960", Some("false")) => self.chess960 = false,
+            _ => bail!("unknown option '{}'", args.join(" ")),
+        }
+        Ok(())
+    }
+
+    /// Starts searching the current position in the background, after stopping the
+    /// previous search if any.
+    fn go(&mut self, limits: SearchLimits) {
+        self.stop_search();
+        self.stop.store(false, Ordering::Relaxed);
+
+        let mut board = self.board.clone();
+        if self.chess960 {
+            // castling is then sent as the king taking its rook
+            board.set_chess960(true);
+        }
+        let history = self.history.clone();
+        let stop = self.stop.clone();
+        let output = self.output.clone();
+        self.search = Some(thread::spawn(move || {
+            search(board, history, limits, stop, output)
+        }));
+    }
+
+    /// Stops the running search, waiting for it to send its best move.
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search) = self.search.take() {
+            let _ = search.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    /// Runs `script` through a UCI session, returning everything it sent.
+    fn run_script(script: &str) -> String {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let output: Output = buffer.clone();
+        Uci::new(output).run(script.as_bytes()).unwrap();
+        let bytes = buffer.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn bestmove(output: &str) -> &str {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("bestmove "))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_uci_session() {
+        let output = run_script("uci\nisready\nucinewgame\nposition startpos\ngo depth 2\nquit\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "id name luna-chess");
+        assert!(lines.contains(&"uciok"));
+        assert!(lines.contains(&"readyok"));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("info depth 1 score cp"))
+        );
+        assert!(output.contains("info depth 2 "));
+        let m = bestmove(&output);
+        assert!(Board::default().parse_uci_move(m).is_ok());
+
+        let output = run_script(
+            "position fen 4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1 moves e1e2 e8e7\ngo depth 3\n",
+        );
+        assert_eq!(bestmove(&output), "d1d5");
+
+        let output = run_script("position fen 4k3/8/8/8/8/8/8/3qK2R w - - 0 1 moves e1e2\ngo\n");
+        assert!(output.contains("info string"));
+
+        let output = run_script("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo wtime 100\n");
+        assert_eq!(bestmove(&output), "0000");
+
+        // an infinite search only sends its best move after stop
+        let output = run_script("go infinite\nisready\nstop\nisready\n");
+        let lines: Vec<&str> = output
+            .lines()
+            .filter(|line| !line.starts_with("info"))
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "readyok");
+        assert!(Board::default().parse_uci_move(bestmove(&output)).is_ok());
+        assert_eq!(lines[2], "readyok");
+
+        let output = run_script(
+            "setoption name UCI_Chess960 value true\nposition fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1 e8d8\ngo\nsetoption name Foo value 1\n",
+        );
+        assert!(!output.contains("info string invalid"));
+        assert!(output.contains("info string unknown option 'name Foo value 1'\n"));
+    }
+
+    #[test]
+    fn test_uci_parse() {
+        let (board, history) = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0], Board::default().get_hash());
+        assert_eq!(
+            board.to_string(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let fen_args: Vec<&str> = ["fen"].into_iter().chain(fen.split(' ')).collect();
+        assert_eq!(parse_position(&fen_args).unwrap().0.to_string(), fen);
+        assert!(parse_position(&["fen"]).is_err());
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_err());
+
+        let limits = SearchLimits::parse(&[
+            "wtime", "60000", "btime", "-20", "winc", "1000", "depth", "5",
+        ])
+        .unwrap();
+        assert_eq!(limits.depth, Some(5));
+        assert_eq!(limits.btime, Some(Duration::ZERO));
+        assert_eq!(
+            limits.time_budget(Color::White),
+            Some(Duration::from_millis(2750))
+        );
+        assert_eq!(limits.time_budget(Color::Black), Some(Duration::ZERO));
+        assert_eq!(
+            SearchLimits::parse(&["depth", "3"])
+                .unwrap()
+                .time_budget(Color::White),
+            None
+        );
+        assert!(SearchLimits::parse(&["movetime"]).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::game::{Game, GameResult, Termination};
+use crate::pgn::result_to_str;
+use crate::search::{Score, Search, SearchLimits};
+use crate::uci::{Output, send};
+
+use anyhow::{Context, Result, bail};
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How long the engine thinks about a move when no clock, `st` or `sd` was given.
+const DEFAULT_MOVETIME: Duration = Duration::from_secs(5);
+
+/// What the main loop of an [`Xboard`] session waits for.
+enum Event {
+    Command(String),
+    /// The end of the input.
+    Eof,
+    /// The move found by the search started as the given generation.
+    Move(usize, Option<ChessMove>),
+}
+
+/// Parses the base time of `level`, in minutes or `minutes:seconds`.
+fn parse_base_time(text: &str) -> Result<Duration> {
+    let (minutes, seconds) = text.split_once(':').unwrap_or((text, "0"));
+    let minutes: u64 = minutes.parse()?;
+    let seconds: u64 = seconds.parse()?;
+    Ok(Duration::from_secs(minutes * 60 + seconds))
+}
+
+/// Returns the `RESULT {comment}` line announcing how the game ended.
+fn result_line(result: GameResult, termination: Termination) -> String {
+    let comment = match (termination, result) {
+        (Termination::Checkmate, GameResult::WhiteWins) => "White mates",
+        (Termination::Checkmate, _) => "Black mates",
+        (Termination::Stalemate, _) => "Stalemate",
+        (Termination::InsufficientMaterial, _) => "Insufficient material",
+        (Termination::FivefoldRepetition, _) => "Fivefold repetition",
+        (Termination::SeventyFiveMoveRule, _) => "75-move rule",
+        (Termination::ThreefoldRepetition, _) => "Threefold repetition",
+        (Termination::FiftyMoveRule, _) => "50-move rule",
+        (Termination::DrawAgreement, _) => "Draw agreed",
+        (Termination::Resignation, _) => "Resignation",
+    };
+    format!("{} {{{}}}", result_to_str(Some(result)), comment)
+}
+
+/// Runs a search of `board` in the background, sending the move found back as an event.
+///
+/// With `post`, every iteration is reported as the depth, the score in centipawns, the
+/// time in centiseconds, the nodes and the principal variation. Mates are reported as
+/// 100000 plus the number of moves, negated when the engine gets mated.
+#[allow(clippy::too_many_arguments)]
+fn search(
+    board: Board,
+    history: Vec<u64>,
+    limits: SearchLimits,
+    post: bool,
+    stop: Arc<AtomicBool>,
+    generation: usize,
+    events: Sender<Event>,
+    output: Output,
+) {
+    let best = Search::new(limits, stop)
+        .with_history(history)
+        .run(&board, |info| {
+            if !post {
+                return;
+            }
+            let score = match info.score {
+                Score::Centipawns(cp) => cp,
+                Score::Mate(moves) if moves > 0 => 100_000 + moves,
+                Score::Mate(moves) => -100_000 + moves,
+            };
+            let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+            send(
+                &output,
+                &format!(
+                    "{} {} {} {} {}",
+                    info.depth,
+                    score,
+                    info.time.as_millis() / 10,
+                    info.nodes,
+                    pv.join(" ")
+                ),
+            );
+        });
+    let _ = events.send(Event::Move(generation, best));
+}
+
+/// The state of a session of the Chess Engine Communication Protocol, the one spoken
+/// by XBoard and WinBoard: the game played so far, the clocks and the running search.
+///
+/// The engine plays the side given by `go`, and only moves by itself when it is not in
+/// force mode and that side is to move.
+pub struct Xboard {
+    game: Game,
+    force: bool,
+    engine_color: Color,
+    post: bool,
+    depth: Option<usize>,
+    movetime: Option<Duration>,
+    /// Moves per time control and increment, from `level`.
+    level: (u32, Duration),
+    engine_time: Option<Duration>,
+    opponent_time: Option<Duration>,
+    output: Output,
+    events: Sender<Event>,
+    receiver: Receiver<Event>,
+    stop: Arc<AtomicBool>,
+    /// Incremented for every search, so the moves of cancelled searches can be ignored.
+    generation: usize,
+    thinking: bool,
+    /// `pong` replies held back until the engine has moved.
+    pending_pongs: Vec<String>,
+}
+
+impl Xboard {
+    pub fn new(output: Output) -> Self {
+        let (events, receiver) = mpsc::channel();
+        Self {
+            game: Game::new(),
+            force: false,
+            engine_color: Color::Black,
+            post: false,
+            depth: None,
+            movetime: None,
+            level: (0, Duration::ZERO),
+            engine_time: None,
+            opponent_time: None,
+            output,
+            events,
+            receiver,
+            stop: Arc::new(AtomicBool::new(false)),
+            generation: 0,
+            thinking: false,
+            pending_pongs: Vec::new(),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or the end of the input, in which case
+    /// the running search is allowed to finish and play its move.
+    pub fn run(&mut self, input: impl BufRead + Send + 'static) -> Result<()> {
+        let events = self.events.clone();
+        thread::spawn(move || {
+            for line in input.lines() {
+                let Ok(line) = line else { break };
+                if events.send(Event::Command(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = events.send(Event::Eof);
+        });
+
+        let mut eof = false;
+        while !eof || self.thinking {
+            match self.receiver.recv()? {
+                Event::Command(line) => {
+                    if !self.handle(&line) {
+                        break;
+                    }
+                }
+                Event::Eof => eof = true,
+                Event::Move(generation, m) => {
+                    if generation == self.generation && self.thinking {
+                        self.thinking = false;
+                        self.play_engine_move(m);
+                    }
+                }
+            }
+        }
+        self.cancel_search();
+        Ok(())
+    }
+
+    /// Handles a single command, returning false on `quit`.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        let result = match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "ics" | "draw" => Ok(()),
+            "protover" => {
+                send(
+                    &self.output,
+                    "feature myname=\"luna-chess\" ping=1 setboard=1 usermove=1 san=0 \
+                     colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 variants=\"normal\" done=1",
+                );
+                Ok(())
+            }
+            "new" => {
+                self.cancel_search();
+                self.game = Game::new();
+                self.force = false;
+                self.engine_color = Color::Black;
+                self.depth = None;
+                Ok(())
+            }
+            "force" => {
+                self.cancel_search();
+                self.force = true;
+                Ok(())
+            }
+            "go" => {
+                self.cancel_search();
+                self.force = false;
+                self.engine_color = self.game.side_to_move();
+                self.think();
+                Ok(())
+            }
+            "playother" => {
+                self.cancel_search();
+                self.force = false;
+                self.engine_color = !self.game.side_to_move();
+                Ok(())
+            }
+            "?" => {
+                // move now
+                self.stop.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            "usermove" => match args {
+                [m] => self.user_move(m),
+                _ => Err(anyhow::anyhow!("usermove expects a move")),
+            },
+            "setboard" => self.set_board(&args.join(" ")),
+            "undo" => {
+                self.cancel_search();
+                self.game.undo();
+                Ok(())
+            }
+            "remove" => {
+                self.cancel_search();
+                self.game.undo();
+                self.game.undo();
+                Ok(())
+            }
+            "level" => self.set_level(args),
+            "st" => args
+                .first()
+                .context("st expects a number of seconds")
+                .and_then(|s| Ok(s.parse::<f64>()?))
+                .map(|secs| self.movetime = Some(Duration::from_secs_f64(secs.max(0.0)))),
+            "sd" => args
+                .first()
+                .context("sd expects a depth")
+                .and_then(|s| Ok(s.parse()?))
+                .map(|depth| self.depth = Some(depth)),
+            "time" | "otim" => args
+                .first()
+                .context("time expects centiseconds")
+                .and_then(|s| Ok(s.parse::<i64>()?))
+                .map(|centis| {
+                    let time = Some(Duration::from_millis(centis.max(0) as u64 * 10));
+                    if command == "time" {
+                        self.engine_time = time;
+                    } else {
+                        self.opponent_time = time;
+                    }
+                }),
+            "post" => {
+                self.post = true;
+                Ok(())
+            }
+            "nopost" => {
+                self.post = false;
+                Ok(())
+            }
+            "result" => {
+                self.cancel_search();
+                self.force = true;
+                Ok(())
+            }
+            "ping" => {
+                let pong = format!("pong {}", args.join(" "));
+                if self.thinking {
+                    self.pending_pongs.push(pong);
+                } else {
+                    send(&self.output, &pong);
+                }
+                Ok(())
+            }
+            "quit" => return false,
+            // moves without the usermove prefix, as sent by older interfaces
+            _ if args.is_empty()
+                && self.game.current_position().parse_uci_move(command).is_ok() =>
+            {
+                self.user_move(command)
+            }
+            _ => {
+                send(
+                    &self.output,
+                    &format!("Error (unknown command): {}", line.trim()),
+                );
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            send(&self.output, &format!("Error ({:#}): {}", err, line.trim()));
+        }
+        true
+    }
+
+    /// Handles `level <moves> <base> <increment>`.
+    fn set_level(&mut self, args: &[&str]) -> Result<()> {
+        let [moves, base, inc] = args else {
+            bail!("level expects moves, base time and increment");
+        };
+        let moves = moves.parse()?;
+        let base = parse_base_time(base)?;
+        let inc = Duration::from_secs_f64(inc.parse::<f64>()?.max(0.0));
+        self.level = (moves, inc);
+        self.movetime = None;
+        self.engine_time = Some(base);
+        self.opponent_time = Some(base);
+        Ok(())
+    }
+
+    fn set_board(&mut self, fen: &str) -> Result<()> {
+        self.cancel_search();
+        let board = Board::from_str(fen).map_err(|err| {
+            send(&self.output, "tellusererror Illegal position");
+            anyhow::Error::from(err)
+        })?;
+        self.game = Game::new_with_board(board);
+        Ok(())
+    }
+
+    /// Plays a move of the opponent, in coordinate notation, and answers it if the
+    /// engine is on move.
+    fn user_move(&mut self, text: &str) -> Result<()> {
+        let board = self.game.current_position();
+        let m = board
+            .parse_uci_move(text)
+            .or_else(|_| board.parse_san(text))
+            .ok()
+            .filter(|_| self.game.result().is_none());
+        let Some(m) = m else {
+            send(&self.output, &format!("Illegal move: {}", text));
+            return Ok(());
+        };
+
+        self.cancel_search();
+        self.game.make_move(m)?;
+        if !self.announce_result() && !self.force && self.game.side_to_move() == self.engine_color {
+            self.think();
+        }
+        Ok(())
+    }
+
+    /// Sends the result if the game just ended, returning whether it did.
+    fn announce_result(&mut self) -> bool {
+        match (self.game.result(), self.game.termination()) {
+            (Some(result), Some(termination)) => {
+                send(&self.output, &result_line(result, termination));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the limits of the next search, from `sd`, `st` or the clocks.
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits {
+            depth: self.depth,
+            movetime: self.movetime,
+            ..SearchLimits::default()
+        };
+        let (moves, inc) = self.level;
+        let (white, black) = match self.engine_color {
+            Color::White => (self.engine_time, self.opponent_time),
+            Color::Black => (self.opponent_time, self.engine_time),
+        };
+        limits.wtime = white;
+        limits.btime = black;
+        limits.winc = inc;
+        limits.binc = inc;
+        if moves > 0 {
+            let played = self
+                .game
+                .current_position()
+                .fullmove_number()
+                .saturating_sub(self.game.start_position().fullmove_number())
+                as u32;
+            limits.movestogo = Some(moves - played % moves);
+        }
+        if limits.depth.is_none() && limits.time_budget(self.engine_color).is_none() {
+            limits.movetime = Some(DEFAULT_MOVETIME);
+        }
+        limits
+    }
+
+    /// Starts searching the current position in the background.
+    fn think(&mut self) {
+        if self.game.result().is_some() {
+            self.announce_result();
+            return;
+        }
+
+        self.generation += 1;
+        self.thinking = true;
+        self.stop = Arc::new(AtomicBool::new(false));
+
+        let board = self.game.current_position().clone();
+        let positions = self.game.positions();
+        let history = positions[..positions.len() - 1]
+            .iter()
+            .map(|board| board.get_hash())
+            .collect();
+        let limits = self.limits();
+        let post = self.post;
+        let stop = self.stop.clone();
+        let generation = self.generation;
+        let events = self.events.clone();
+        let output = self.output.clone();
+        thread::spawn(move || {
+            search(
+                board, history, limits, post, stop, generation, events, output,
+            )
+        });
+    }
+
+    /// Stops the running search, if any, throwing its move away.
+    fn cancel_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.generation += 1;
+        if self.thinking {
+            self.thinking = false;
+            self.flush_pongs();
+        }
+    }
+
+    fn play_engine_move(&mut self, m: Option<ChessMove>) {
+        if let Some(m) = m
+            && self.game.make_move(m).is_ok()
+        {
+            send(&self.output, &format!("move {}", m));
+        }
+        self.announce_result();
+        self.flush_pongs();
+    }
+
+    fn flush_pongs(&mut self) {
+        for pong in std::mem::take(&mut self.pending_pongs) {
+            send(&self.output, &pong);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::Mutex;
+
+    /// Runs `script` through an XBoard session, returning the lines it sent.
+    fn run_script(script: &str) -> Vec<String> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let output: Output = buffer.clone();
+        Xboard::new(output)
+            .run(Cursor::new(script.to_string()))
+            .unwrap();
+        let bytes = buffer.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_xboard_session() {
+        let lines = run_script("xboard\nprotover 2\nnew\nsd 2\nusermove e2e4\nping 1\n");
+        assert!(lines[0].starts_with("feature "));
+        assert!(lines[0].ends_with("done=1"));
+        assert!(lines[1].starts_with("move "));
+        assert_eq!(lines[2], "pong 1");
+        assert_eq!(lines.len(), 3);
+
+        // in force mode the engine only records the moves
+        let lines = run_script("new\nforce\nsd 2\nusermove e2e4\nusermove e7e5\nping 2\ngo\n");
+        assert_eq!(lines[0], "pong 2");
+        assert!(lines[1].starts_with("move "));
+        assert_eq!(lines.len(), 2);
+
+        let lines = run_script("new\nforce\ne2e4\nusermove e2e4\nfoo\nsd x\n");
+        assert_eq!(
+            lines,
+            [
+                "Illegal move: e2e4",
+                "Error (unknown command): foo",
+                "Error (invalid digit found in string): sd x"
+            ]
+        );
+
+        let lines = run_script(
+            "new\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\nforce\nusermove a1a8\nping 3\n",
+        );
+        assert_eq!(lines, ["1-0 {White mates}", "pong 3"]);
+
+        let lines = run_script("new\nsd 3\npost\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo\n");
+        assert!(lines[0].starts_with("1 100001 "));
+        assert!(lines[0].ends_with(" a1a8"));
+        assert_eq!(lines[1..], ["move a1a8", "1-0 {White mates}"]);
+
+        let lines = run_script("setboard 8/8/8/8/8/8/8/8 w - - 0 1\n");
+        assert_eq!(lines[0], "tellusererror Illegal position");
+    }
+
+    #[test]
+    fn test_xboard_undo_and_clocks() {
+        let mut xboard = Xboard::new(Arc::new(Mutex::new(Vec::new())));
+        for line in [
+            "new",
+            "force",
+            "usermove e2e4",
+            "usermove e7e5",
+            "usermove g1f3",
+            "undo",
+            "level 40 5 2",
+            "time 6000",
+            "otim 3000",
+        ] {
+            xboard.handle(line);
+        }
+        assert_eq!(xboard.game.moves().len(), 2);
+        xboard.handle("remove");
+        assert!(xboard.game.moves().is_empty());
+
+        let limits = xboard.limits();
+        assert_eq!(limits.btime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.wtime, Some(Duration::from_secs(30)));
+        assert_eq!(limits.binc, Duration::from_secs(2));
+        assert_eq!(limits.movestogo, Some(40));
+
+        xboard.handle("st 5");
+        xboard.handle("sd 7");
+        let limits = xboard.limits();
+        assert_eq!(limits.movetime, Some(Duration::from_secs(5)));
+        assert_eq!(limits.depth, Some(7));
+        assert_eq!(parse_base_time("0:30").unwrap(), Duration::from_secs(30));
+    }
+}
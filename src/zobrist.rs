@@ -0,0 +1,44 @@
+use crate::castle_rights::CastleRights;
+use crate::color::Color;
+use crate::file::File;
+use crate::pieces::Piece;
+use crate::square::Square;
+
+include!(concat!(env!("OUT_DIR"), "/zobrist_file.rs"));
+
+/// Returns the key of a `piece` of the given `color` standing on `square`.
+#[inline(always)]
+pub fn get_piece_key(piece: Piece, square: Square, color: Color) -> u64 {
+    unsafe {
+        *ZOBRIST_PIECES
+            .get_unchecked(color.to_index())
+            .get_unchecked(piece.to_index())
+            .get_unchecked(square.to_index())
+    }
+}
+
+/// Returns the combined key of all the castling rights that are set.
+#[inline(always)]
+pub fn get_castles_key(rights: CastleRights) -> u64 {
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        if rights.has_kingside(color) {
+            key ^= ZOBRIST_CASTLES[color.to_index()][0];
+        }
+        if rights.has_queenside(color) {
+            key ^= ZOBRIST_CASTLES[color.to_index()][1];
+        }
+    }
+    key
+}
+
+#[inline(always)]
+pub fn get_en_passant_key(file: File) -> u64 {
+    unsafe { *ZOBRIST_EN_PASSANT.get_unchecked(file.to_index()) }
+}
+
+/// Returns the key toggled every time the side to move changes.
+#[inline(always)]
+pub fn get_side_key() -> u64 {
+    ZOBRIST_SIDE
+}
@@ -0,0 +1,47 @@
+use luna_chess::epd::Epd;
+use luna_chess::movegen::MoveGen;
+use std::fs;
+use std::process::Command;
+use std::str::FromStr;
+
+#[test]
+fn perftsuite() {
+    for line in include_str!("perftsuite.epd").lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let epd = Epd::from_str(line).unwrap();
+        for (depth, nodes) in epd.perft_counts() {
+            assert_eq!(
+                MoveGen::perft_test(epd.board(), depth) as u64,
+                nodes,
+                "D{} of {}",
+                depth,
+                line
+            );
+        }
+    }
+}
+
+#[test]
+fn perftsuite_binary() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_perftsuite"))
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    let suite = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/perftsuite.epd");
+    assert_eq!(run(&[suite, "1"]), Some(0));
+    assert_eq!(run(&[suite, "0"]), Some(2));
+
+    // a suite without any count checks nothing
+    let path = std::env::temp_dir().join(format!("perftsuite-{}.epd", std::process::id()));
+    fs::write(&path, "4k3/8/8/8/8/8/8/4K3 w - - 0 1\n").unwrap();
+    let code = run(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, Some(1));
+}
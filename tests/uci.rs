@@ -0,0 +1,25 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn uci_binary() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_luna-uci"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    engine
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"uci\nisready\nposition startpos moves e2e4\ngo wtime 1000 btime 1000\nquit\n")
+        .unwrap();
+
+    let output = engine.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.contains(&"uciok"));
+    assert!(lines.contains(&"readyok"));
+    assert!(lines.last().unwrap().starts_with("bestmove "));
+}